@@ -1,6 +1,15 @@
 mod command_messages;
 
+use std::collections::HashMap;
+
+use lib::params::{
+    ControlParams, FgParams, RestartParams, SendStdinParams, SignalParams, Targets, WaitParams,
+    WatchParams,
+};
+use lib::process_status::ProcessState;
 use lib::TM_VERSION;
+use serde::Serialize;
+use serde_json::Value;
 
 fn check_argument_syntax<'a>(words: Vec<&'a str>, help: Vec<&'a str>) -> Vec<&'a str> {
     for w in &words[1..] {
@@ -10,8 +19,9 @@ fn check_argument_syntax<'a>(words: Vec<&'a str>, help: Vec<&'a str>) -> Vec<&'a
         }
         match w.split_once(":") {
             None => return help,
+            Some((_, "*")) => {}
             Some((_, seq)) => {
-                if let Err(_) = seq.parse::<u32>() {
+                if seq.parse::<u32>().is_err() {
                     return help;
                 }
             }
@@ -32,30 +42,270 @@ fn check_arguments(error_message: String, words: Vec<&str>, count: usize) -> Vec
     check_argument_syntax(words, help)
 }
 
+fn check_target_with_text(error_message: String, words: Vec<&str>) -> Vec<&str> {
+    let help = vec!["help", words[0]];
+    if words.len() < 3 {
+        println!("{}", error_message);
+        return help;
+    }
+    if check_argument_syntax(words[..2].to_vec(), help.clone()) == help {
+        return help;
+    }
+    words
+}
+
+fn check_signal_arguments(error_message: String, words: Vec<&str>) -> Vec<&str> {
+    let help = vec!["help", words[0]];
+    if words.len() < 3 {
+        println!("{}", error_message);
+        return help;
+    }
+    let mut targets = vec![words[0]];
+    targets.extend_from_slice(&words[2..]);
+    if check_argument_syntax(targets, help.clone()) == help {
+        return help;
+    }
+    words
+}
+
+// <command> [--option [n]]... <name:seq>, the options listed with true take a number
+fn check_options_arguments<'a>(words: Vec<&'a str>, options: &[(&str, bool)]) -> Vec<&'a str> {
+    let help = vec!["help", words[0]];
+    let mut targets = vec![words[0]];
+    let mut given = Vec::new();
+    let mut iter = words[1..].iter();
+    while let Some(w) = iter.next() {
+        let Some(key) = w.strip_prefix("--") else {
+            targets.push(w);
+            continue;
+        };
+        given.push(key);
+        match options.iter().find(|(k, _)| *k == key) {
+            Some((_, false)) => {}
+            Some((_, true)) => match iter.next().map(|n| n.parse::<u32>()) {
+                Some(Ok(n)) if n > 0 => {}
+                _ => {
+                    println!("Error: --{key} requires a number");
+                    return help;
+                }
+            },
+            None => {
+                println!("Error: unknown option {w}");
+                return help;
+            }
+        }
+    }
+    if let Some(e) = check_options_combination(&given) {
+        println!("Error: {e}");
+        return help;
+    }
+    let error_message = format!("Error: {} requires a process name", words[0]);
+    if check_arguments(error_message, targets, 1) == help {
+        return help;
+    }
+    words
+}
+
+// a rolling restart waits batch by batch by itself
+fn check_options_combination(given: &[&str]) -> Option<&'static str> {
+    let has = |key: &str| given.contains(&key);
+    if has("timeout") && !has("wait") {
+        Some("--timeout goes with --wait")
+    } else if has("rolling") && has("wait") {
+        Some("--rolling waits by itself, without --wait")
+    } else if (has("batch") || has("wait-running")) && !has("rolling") {
+        Some("--batch and --wait-running go with --rolling")
+    } else {
+        None
+    }
+}
+
+// wait <state> [--timeout n] <name:seq>...
+fn check_wait_arguments(words: Vec<&str>) -> Vec<&str> {
+    let help = vec!["help", words[0]];
+    if words.len() < 3 || words[1].parse::<ProcessState>().is_err() {
+        println!("Error: wait requires a state and a process name");
+        return help;
+    }
+    let mut targets = vec![words[0]];
+    let mut iter = words[2..].iter();
+    while let Some(w) = iter.next() {
+        match *w {
+            "--timeout" => match iter.next().map(|n| n.parse::<u32>()) {
+                Some(Ok(n)) if n > 0 => {}
+                _ => {
+                    println!("Error: --timeout requires a number");
+                    return help;
+                }
+            },
+            w => targets.push(w),
+        }
+    }
+    if targets.len() < 2 || check_argument_syntax(targets, help.clone()) == help {
+        return help;
+    }
+    words
+}
+
+fn check_watch_arguments(error_message: String, words: Vec<&str>) -> Vec<&str> {
+    if words.len() >= 2 && words[1] != "pause" && words[1] != "resume" {
+        println!("{}", error_message);
+        return vec!["help", words[0]];
+    }
+    check_signal_arguments(error_message, words)
+}
+
 pub fn check_command(line: &str) -> Result<Vec<&str>, ()> {
     let words: Vec<&str> = line.split(' ').filter(|x| !x.is_empty()).collect();
 
     match words[0] {
-        "version" | "reload" => Ok(check_arguments(
+        "version" | "reload" | "reopenlogs" | "reexec" => Ok(check_arguments(
             format!("Error: {} accepts no arguments", words[0].to_owned()),
             words,
             0,
         )),
         "open" => Ok(check_arguments(
-            format!("ERROR: path must be /path/to/socket"),
+            "ERROR: path must be /path/to/socket".to_string(),
             words,
             1,
         )),
-        "start" | "stop" | "restart" => Ok(check_arguments(
-            format!("Error: {} requires a process name", words[0].to_owned()),
+        "fg" => Ok(check_arguments(
+            "Error: no process name supplied".to_string(),
             words,
             1,
         )),
+        "start" | "stop" => Ok(check_options_arguments(
+            words,
+            &[("wait", false), ("timeout", true)],
+        )),
+        "restart" => Ok(check_options_arguments(
+            words,
+            &[
+                ("wait", false),
+                ("timeout", true),
+                ("rolling", false),
+                ("batch", true),
+                ("wait-running", false),
+            ],
+        )),
+        "signal" => Ok(check_signal_arguments(
+            "Error: signal requires a signal name and a process name".to_string(),
+            words,
+        )),
+        "wait" => Ok(check_wait_arguments(words)),
+        "watch" => Ok(check_watch_arguments(
+            "Error: watch requires pause or resume and a process name".to_string(),
+            words,
+        )),
+        "sendstdin" => Ok(check_target_with_text(
+            "Error: sendstdin requires a process name and a text".to_string(),
+            words,
+        )),
         "status" | "shutdown" | "update" | "quit" | "exit" | "help" => Ok(words),
         _ => Err(()),
     }
 }
 
+// the names among the arguments of a checked command, and its options with their number
+fn split_options<'a>(args: &[&'a str]) -> (Vec<String>, HashMap<&'a str, Option<u64>>) {
+    let mut names = Vec::new();
+    let mut options = HashMap::new();
+    let mut iter = args.iter();
+    while let Some(w) = iter.next() {
+        match w.strip_prefix("--") {
+            Some(key @ ("timeout" | "batch")) => {
+                options.insert(key, iter.next().and_then(|n| n.parse().ok()));
+            }
+            Some(key) => {
+                options.insert(key, None);
+            }
+            None => names.push(w.to_string()),
+        }
+    }
+    (names, options)
+}
+
+fn to_value<P: Serialize>(params: P) -> Value {
+    serde_json::to_value(params).unwrap_or_default()
+}
+
+// the params of the method a command checked by check_command calls
+pub fn params(words: &[&str]) -> Value {
+    let args = &words[1..];
+    let names = |args: &[&str]| args.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+    match words[0] {
+        "status" => to_value(Targets { names: names(args) }),
+        "start" | "stop" => {
+            let (names, options) = split_options(args);
+            to_value(ControlParams {
+                names,
+                wait: options.contains_key("wait"),
+                timeout: options.get("timeout").copied().flatten(),
+            })
+        }
+        "restart" => {
+            let (names, options) = split_options(args);
+            to_value(RestartParams {
+                names,
+                wait: options.contains_key("wait"),
+                timeout: options.get("timeout").copied().flatten(),
+                rolling: options.contains_key("rolling"),
+                batch: options.get("batch").copied().flatten(),
+                wait_running: options.contains_key("wait-running"),
+            })
+        }
+        "signal" => to_value(SignalParams {
+            signal: args[0].to_owned(),
+            names: names(&args[1..]),
+        }),
+        "sendstdin" => to_value(SendStdinParams {
+            name: args[0].to_owned(),
+            text: args[1..].join(" "),
+        }),
+        "watch" => to_value(WatchParams {
+            paused: args[0] == "pause",
+            names: names(&args[1..]),
+        }),
+        "wait" => {
+            let (names, options) = split_options(&args[1..]);
+            to_value(WaitParams {
+                state: args[0].parse().unwrap_or(ProcessState::Running),
+                names,
+                timeout: options.get("timeout").copied().flatten(),
+            })
+        }
+        "fg" => to_value(FgParams {
+            name: args[0].to_owned(),
+        }),
+        _ => Value::Null,
+    }
+}
+
+pub fn is_command(word: &str) -> bool {
+    matches!(
+        word,
+        "version"
+            | "reload"
+            | "reopenlogs"
+            | "reexec"
+            | "open"
+            | "fg"
+            | "start"
+            | "stop"
+            | "restart"
+            | "signal"
+            | "sendstdin"
+            | "wait"
+            | "watch"
+            | "status"
+            | "shutdown"
+            | "update"
+            | "quit"
+            | "exit"
+            | "help"
+    )
+}
+
 pub fn print_help(words: Vec<&str>) {
     if words.len() == 1 {
         println!("{}", command_messages::HELP);
@@ -69,10 +319,17 @@ pub fn print_help(words: Vec<&str>) {
         "open" => println!("{}", command_messages::HELP_OPEN),
         "reload" => println!("{}", command_messages::HELP_RELOAD),
         "shutdown" => println!("{}", command_messages::HELP_SHUTDOWN),
+        "reopenlogs" => println!("{}", command_messages::HELP_REOPENLOGS),
+        "reexec" => println!("{}", command_messages::HELP_REEXEC),
         "update" => println!("{}", command_messages::HELP_UPDATE),
         "quit" => println!("{}", command_messages::HELP_QUIT),
         "exit" => println!("{}", command_messages::HELP_EXIT),
         "version" => println!("{}", command_messages::HELP_VERSION),
+        "sendstdin" => println!("{}", command_messages::HELP_SENDSTDIN),
+        "fg" => println!("{}", command_messages::HELP_FG),
+        "signal" => println!("{}", command_messages::HELP_SIGNAL),
+        "wait" => println!("{}", command_messages::HELP_WAIT),
+        "watch" => println!("{}", command_messages::HELP_WATCH),
         "help" => println!("{}", command_messages::HELP_HELP),
         _ => {
             let s = words[1..].join(" ");
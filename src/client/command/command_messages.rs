@@ -4,24 +4,26 @@
 // add      exit     open     reload   restart  start    avail
 // remove   shutdown status   update   log      quit     stop     version";
 
-pub const HELP: &'static str = "\
+pub const HELP: &str = "\
 default commands (type help <topic>):
 =====================================
-exit     open     reload   restart  start    shutdown
-status   update   quit     stop     version";
+exit     fg       open     reexec   reload   reopenlogs
+restart  sendstdin signal  start    shutdown status
+update   quit     stop     version  wait
+watch";
 
-pub const HELP_HELP: &'static str = "\
+pub const HELP_HELP: &str = "\
 help \t\tPrint a list of available actions\nhelp <action>\tPrint help for <action>";
 
 // pub const HELP_AVAIL: &'static str = "avail\t\t\tDisplay all configured processes";
-pub const HELP_QUIT: &'static str = "quit\tExit the supervisor shell.";
-pub const HELP_EXIT: &'static str = "exit\tExit the supervisor shell.";
-pub const HELP_SHUTDOWN: &'static str = "shutdown\t\tShut the remote taskmasterd down.";
+pub const HELP_QUIT: &str = "quit\tExit the supervisor shell.";
+pub const HELP_EXIT: &str = "exit\tExit the supervisor shell.";
+pub const HELP_SHUTDOWN: &str = "shutdown\t\tShut the remote taskmasterd down.";
 
-pub const HELP_VERSION: &'static str = "\
+pub const HELP_VERSION: &str = "\
 version\t\t\tShow the version of the remote taskmasterd process";
 
-pub const HELP_UPDATE: &'static str = "\
+pub const HELP_UPDATE: &str = "\
 update\t\t\tReload config and add/remove as necessary, and will restart affected programs";
 
 // pub const HELP_ADD: &'static str = "\
@@ -30,30 +32,76 @@ update\t\t\tReload config and add/remove as necessary, and will restart affected
 // pub const HELP_REMOVE: &'static str = "\
 // remove <name:seq> [...]	Removes process/group from active config";
 
-pub const HELP_STATUS: &'static str = "\
+pub const HELP_STATUS: &str = "\
 status <name:seq>		Get status for a single process
 status <name:seq> <name:seq>	Get status for multiple named processes
 status				Get all process status info";
 
-pub const HELP_STOP: &'static str = "\
+pub const HELP_STOP: &str = "\
 stop <name:seq>			Stop a process
 stop <name:seq> <name:seq>	Stop multiple processes or groups
-stop all			Stop all processes";
+stop all			Stop all processes
+stop --wait <name:seq>		Answer once stopped, or killed after stopwaitsecs
+	--timeout <secs>	Give up waiting after secs (default 30)";
 
-pub const HELP_RESTART: &'static str = "\
+pub const HELP_RESTART: &str = "\
 restart <name:seq>		Restart a process
 restart <name:seq> <name:seq>	Restart multiple processes or groups
 restart all			Restart all processes
+restart --rolling <name:*>	Restart one process at a time, the next one
+				once the previous one is Running again
+	--batch <n>		Restart n processes at a time
+	--wait-running		Also wait until they are healthy (Completed if oneshot)
+				A process going Fatal aborts the rolling restart
+restart --wait <name:seq>	Answer with the state the processes ended up in
+	--timeout <secs>	Give up waiting after secs (default 30)
 Note: restart does not update config files. For that, see update.";
 
-pub const HELP_START: &'static str = "\
+pub const HELP_START: &str = "\
 start <name:seq>		Start a process
 start <name:seq> <name:seq>	Start multiple processes or groups
-start all			Start all processes";
+start all			Start all processes
+start --wait <name:seq>		Answer with the state the processes ended up in
+	--timeout <secs>	Give up waiting after secs (default 30)";
 
-pub const HELP_OPEN: &'static str = "\
+pub const HELP_SENDSTDIN: &str = "\
+sendstdin <name:seq> <text>	Write a line of text to the stdin of a process
+				(the program should be configured with stdin=pipe)";
+
+pub const HELP_SIGNAL: &str = "\
+signal <signal name> <name:seq>		Signal a process
+signal <signal name> <name:*>		Signal every process of a program
+signal <signal name> <name:seq> <name:seq>	Signal multiple processes
+signal <signal name> all		Signal all processes";
+
+pub const HELP_WAIT: &str = "\
+wait <state> <name:seq>		Wait until a process is in state (e.g. running)
+wait <state> <name:seq> <name:seq>	Wait until all of them are
+wait <state> <name:*>		Wait for every process of a program
+	--timeout <secs>	Give up after secs (default 30)
+From the command line (tmctl wait running web:*), the exit code is 0 if they got there";
+
+pub const HELP_WATCH: &str = "\
+watch pause <name:seq>		Stop restarting a process when its watched files change
+watch resume <name:seq>		Restart it on changes again
+watch pause <name:*>		Pause watching for every process of a program
+watch pause all			Pause watching for all processes";
+
+pub const HELP_FG: &str = "\
+fg <name:seq>	Connect to a process in foreground mode
+		Ctrl-C to exit";
+
+pub const HELP_OPEN: &str = "\
 open <path> 	Connect to a remote taskmasterd process.
 		(for UNIX domain socket, use /path/to/socket)";
 
-pub const HELP_RELOAD: &'static str = "\
+pub const HELP_REEXEC: &str = "\
+reexec 		Replace the remote taskmasterd with a new one (e.g. after an upgrade).
+		Running processes are handed over and keep running.";
+
+pub const HELP_REOPENLOGS: &str = "\
+reopenlogs	Reopen the log files of taskmasterd and its processes
+		(same as sending SIGUSR2 to taskmasterd)";
+
+pub const HELP_RELOAD: &str = "\
 reload 		Restart the remote taskmasterd.";
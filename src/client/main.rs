@@ -14,17 +14,53 @@ use std::process;
 use net::Net;
 use terminal::Terminal;
 
+// tmctl [conf_file] <command>: run a single command for scripts, the exit code
+// is 0 if it succeeded, 1 if it failed and 2 if it was not understood
+fn run_once(net: &mut Net, line: &str) -> i32 {
+    let words = match command::check_command(line) {
+        Ok(words) => words,
+        Err(_) => {
+            eprintln!("*** Unknown syntax: {line}");
+            return 2;
+        }
+    };
+    let command = words[0];
+    match command {
+        "help" => {
+            let asked = line.starts_with("help");
+            command::print_help(words);
+            if asked {
+                0
+            } else {
+                2
+            }
+        }
+        "version" => {
+            command::print_version();
+            0
+        }
+        "open" | "fg" | "exit" | "quit" => {
+            eprintln!("{command} is only available in the shell");
+            2
+        }
+        _ if net.communicate_with_server(words) => 0,
+        _ => 1,
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
 
-    let conf_file = match args.len() {
-        1 => CONF_FILE,
-        2 => &args[1],
-        _ => {
-            eprintln!("usage: {} [conf_file]", &args[0]);
+    let (conf_file, once) = match args.get(1) {
+        None => (CONF_FILE, &args[1..]),
+        Some(a) if command::is_command(a) => (CONF_FILE, &args[1..]),
+        Some(a) if a.starts_with('-') => {
+            eprintln!("usage: {} [conf_file] [command [arguments]]", &args[0]);
             eprintln!("if conf_file is missing, default ({CONF_FILE}) will be used.");
-            std::process::exit(1);
+            eprintln!("without a command, an interactive shell is started.");
+            std::process::exit(2);
         }
+        Some(a) => (a.as_str(), &args[2..]),
     };
 
     let conf = Config::from(conf_file).unwrap_or_else(|e| {
@@ -32,8 +68,13 @@ fn main() -> Result<(), Box<dyn Error>> {
         std::process::exit(1);
     });
 
-    let mut terminal = Terminal::new("taskmaster>");
     let mut net = Net::new(&conf.general.sockfile);
+    if !once.is_empty() {
+        process::exit(run_once(&mut net, &once.join(" ")));
+    }
+
+    let mut terminal = Terminal::new("taskmaster>");
+    net.communicate_with_server(vec!["status"]);
 
     loop {
         let line = terminal.getline()?;
@@ -49,8 +90,11 @@ fn main() -> Result<(), Box<dyn Error>> {
                     net.open(words[1]);
                     net.communicate_with_server(vec!["status"]);
                 }
+                "fg" => net.attach(words, &mut terminal),
                 "exit" | "quit" => process::exit(0),
-                _ => net.communicate_with_server(words),
+                _ => {
+                    net.communicate_with_server(words);
+                }
             },
         }
     }
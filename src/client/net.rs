@@ -1,92 +1,241 @@
 use std::io::prelude::*;
-use std::io::ErrorKind;
+use std::io::{self, BufReader, ErrorKind};
 use std::net::Shutdown;
 use std::os::unix::net::UnixStream;
+use std::thread;
 
+use lib::protocol::{read_message, write_message, Hello, Reply, PROTOCOL_VERSION};
 use lib::request::Request;
-use lib::response::Response;
+use lib::response::{Error as RpcError, Response};
 
+use crate::command;
+use crate::terminal::Terminal;
+
+// one connection serves every command until it breaks or a session (fg) takes it over
 pub struct Net {
     sock_path: String,
-    stream: Option<UnixStream>,
+    stream: Option<BufReader<UnixStream>>,
+    next_id: u64,
 }
 
 impl Net {
     pub fn new(sock_path: &str) -> Self {
-        let mut net = Net {
+        Net {
             sock_path: sock_path.to_owned(),
             stream: None,
-        };
-        net.communicate_with_server(vec!["status"]);
-        net
+            next_id: 1,
+        }
     }
 
     fn connect(sock_path: &str) -> Option<UnixStream> {
         let ret = UnixStream::connect(sock_path).ok();
-        if let None = ret {
+        if ret.is_none() {
             eprintln!("{sock_path} refused connection");
         }
         ret
     }
 
+    // a taskmasterd from before the handshake answers with an error right away
+    fn handshake(stream: UnixStream) -> Result<BufReader<UnixStream>, std::io::Error> {
+        write_message(&stream, &Hello::new())?;
+        let mut reader = BufReader::new(stream);
+        let version = match read_message::<Hello>(&mut reader) {
+            Ok(Some(hello)) => hello.version,
+            Ok(None) | Err(_) => 0,
+        };
+        if version != PROTOCOL_VERSION {
+            let e = RpcError::ProtocolMismatch(PROTOCOL_VERSION, version);
+            return Err(std::io::Error::new(ErrorKind::Unsupported, e.to_string()));
+        }
+        Ok(reader)
+    }
+
+    fn ensure_connected(&mut self) -> bool {
+        if self.stream.is_some() {
+            return true;
+        }
+        let Some(stream) = Net::connect(self.sock_path.as_str()) else {
+            return false;
+        };
+        match Net::handshake(stream) {
+            Ok(reader) => {
+                self.stream = Some(reader);
+                true
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                false
+            }
+        }
+    }
+
     pub fn open(&mut self, sock_path: &str) {
+        self.disconnect();
         self.sock_path = sock_path.to_owned();
-        self.stream = Net::connect(sock_path); // 이전 소켓 있을 때?
+        self.ensure_connected();
     }
 
     fn disconnect(&mut self) {
-        let stream = match self.stream {
-            Some(ref sock) => sock,
-            None => return,
-        };
-
-        stream
-            .shutdown(Shutdown::Both)
-            .expect("connection shutdown failed");
+        if let Some(stream) = self.stream.take() {
+            stream
+                .get_ref()
+                .shutdown(Shutdown::Both)
+                .unwrap_or_default();
+        }
     }
 
-    fn send_command(&mut self, words: Vec<&str>) -> Result<(), std::io::Error> {
-        let req = Request::from(&words);
-        let mut stream = self.stream.as_ref().ok_or(std::io::Error::new(
+    fn stream(&mut self) -> Result<&mut BufReader<UnixStream>, std::io::Error> {
+        self.stream.as_mut().ok_or(std::io::Error::new(
             ErrorKind::NotConnected,
-            format!("not connected"),
-        ))?;
+            "not connected".to_string(),
+        ))
+    }
 
-        let line: String = serde_json::to_string::<Request>(&req)?;
-        stream.write_all(line.as_bytes())?;
-        Ok(())
+    // returns the id the reply comes with
+    fn send(&mut self, words: &Vec<&str>) -> Result<u64, std::io::Error> {
+        let req = Request::new(self.next_id, words[0], command::params(words));
+        write_message(self.stream()?.get_ref(), &req)?;
+        self.next_id += 1;
+        Ok(req.id)
     }
 
-    fn recv_response(&mut self) -> Result<(), std::io::Error> {
-        let mut stream = self.stream.as_ref().ok_or(std::io::Error::new(
-            ErrorKind::NotConnected,
-            format!("not connected"),
-        ))?;
+    // None if the connection was closed before the reply
+    fn receive(&mut self, id: u64) -> Result<Option<Response>, std::io::Error> {
+        let Some(reply) = read_message::<Reply>(self.stream()?)? else {
+            return Ok(None);
+        };
+        if reply.id != id {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("reply to request {} instead of {id}", reply.id),
+            ));
+        }
+        Ok(Some(reply.response))
+    }
 
-        let mut line = String::new();
-        stream.read_to_string(&mut line)?;
-        let responses = serde_json::from_str::<Response>(&line)?;
+    fn request(&mut self, words: &Vec<&str>) -> Result<Option<Response>, std::io::Error> {
+        let id = self.send(words)?;
+        self.receive(id)
+    }
 
+    // returns false if the response contains an error
+    fn print_response(responses: &Response) -> bool {
         match responses {
-            Response::Action(act) => act.list.iter().for_each(|res| match res {
-                Ok(o) => println!("{o}"),
-                Err(e) => eprintln!("{e}"),
+            Response::Action(act) => act.list.iter().fold(true, |ok, res| match res {
+                Ok(o) => {
+                    println!("{o}");
+                    ok
+                }
+                Err(e) => {
+                    eprintln!("{e}");
+                    false
+                }
             }),
-            Response::Status(stat) => stat.iter().for_each(|x| println!("{x}")),
+            Response::Status(stat) => {
+                stat.iter().for_each(|x| println!("{x}"));
+                true
+            }
+        }
+    }
+
+    fn forward_output(mut stream: BufReader<UnixStream>) {
+        let mut buf = [0u8; 1024];
+        let mut stdout = io::stdout();
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if stdout
+                        .write_all(&buf[..n])
+                        .and_then(|_| stdout.flush())
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn forward_input(
+        mut stream: &UnixStream,
+        terminal: &mut Terminal,
+    ) -> Result<(), std::io::Error> {
+        while let Some(mut line) = terminal.getline_detachable()? {
+            line.push('\n');
+            stream.write_all(line.as_bytes())?;
         }
         Ok(())
     }
 
-    pub fn communicate_with_server(&mut self, words: Vec<&str>) {
-        self.stream = Net::connect(self.sock_path.as_str());
-        if let Err(e) = self.send_command(words) {
-            eprintln!("Service temporary unavailable: {e}");
-            self.disconnect();
+    // the first response of a session tells whether it was accepted
+    pub fn attach(&mut self, words: Vec<&str>, terminal: &mut Terminal) {
+        if !self.ensure_connected() {
             return;
         }
-        if let Err(e) = self.recv_response() {
-            eprintln!("Service temporary unavailable: {e}");
-            self.disconnect();
+        match self.request(&words) {
+            Ok(Some(res)) if Net::print_response(&res) => {}
+            Ok(Some(_)) => return,
+            Ok(None) => {
+                eprintln!("Service temporary unavailable: connection closed");
+                return self.disconnect();
+            }
+            Err(e) => {
+                eprintln!("Service temporary unavailable: {e}");
+                return self.disconnect();
+            }
+        }
+
+        // the connection belongs to the session from now on
+        let Some(reader) = self.stream.take() else {
+            return;
+        };
+        let writer = match reader.get_ref().try_clone() {
+            Ok(writer) => writer,
+            Err(_) => return,
+        };
+        println!("==> Press Ctrl-C to exit <==");
+        let output = thread::spawn(move || Net::forward_output(reader));
+
+        if let Err(e) = Net::forward_input(&writer, terminal) {
+            eprintln!("{e}");
+        }
+        writer.shutdown(Shutdown::Both).unwrap_or_default();
+        output.join().unwrap_or_default();
+    }
+
+    // returns false if the command failed
+    pub fn communicate_with_server(&mut self, words: Vec<&str>) -> bool {
+        // taskmasterd closes the connections left idle when it is replaced (reexec),
+        // writing to such a connection fails and a new one is tried once. a request
+        // which went out is never sent again, it may have been carried out
+        let reused = self.stream.is_some();
+        if !self.ensure_connected() {
+            return false;
+        }
+        let sent = match self.send(&words) {
+            Err(_) if reused => {
+                self.disconnect();
+                if !self.ensure_connected() {
+                    return false;
+                }
+                self.send(&words)
+            }
+            sent => sent,
+        };
+        let res = sent.and_then(|id| self.receive(id));
+        match res {
+            Ok(Some(res)) => Net::print_response(&res),
+            Ok(None) => {
+                eprintln!("Service temporary unavailable: connection closed");
+                self.disconnect();
+                false
+            }
+            Err(e) => {
+                eprintln!("Service temporary unavailable: {e}");
+                self.disconnect();
+                false
+            }
         }
     }
 }
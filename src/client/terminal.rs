@@ -21,13 +21,14 @@ const DELETE2: u8 = 0x7e;
 const KEY_HOME: u8 = 0x48;
 const KEY_END: u8 = 0x46;
 const BACKSPACE: u8 = 0x7f;
+const CTRL_C: u8 = 0x03;
 
 #[derive(Debug)]
 enum LineState {
-    CHAR,
-    ESCAPE,
-    ARROW,
-    DELETE,
+    Char,
+    Escape,
+    Arrow,
+    Delete,
 }
 
 pub struct Terminal {
@@ -98,46 +99,45 @@ impl Terminal {
 
     pub fn getline(&mut self) -> Result<String, errno::Errno> {
         self.line_init();
-        let mut state = LineState::CHAR;
+        let mut state = LineState::Char;
         loop {
             let ch = ffi::getch()?;
             // println!("{ch}");
             let line_editor = &mut self.history[self.history_index];
 
             match state {
-                LineState::CHAR => match ch {
+                LineState::Char => match ch {
                     ENTER => break,
-                    ESCAPE => state = LineState::ESCAPE,
+                    ESCAPE => state = LineState::Escape,
                     BACKSPACE => line_editor.delete_char_prev(),
                     0x20..=0x7e => line_editor.print_char(ch),
                     _ => {}
                 },
-                LineState::ESCAPE => match ch {
-                    BRACKET => state = LineState::ARROW,
-                    ESCAPE => state = LineState::CHAR,
+                LineState::Escape => match ch {
+                    BRACKET => state = LineState::Arrow,
+                    ESCAPE => state = LineState::Char,
                     _ => {
                         line_editor.print_char(ch);
-                        state = LineState::CHAR
+                        state = LineState::Char
                     }
                 },
-                LineState::ARROW => {
-                    state = LineState::CHAR;
+                LineState::Arrow => {
+                    state = LineState::Char;
                     match ch {
                         UP => self.history_prev(),
                         DOWN => self.history_next(),
                         LEFT => line_editor.move_cursor_left(1),
                         RIGHT => line_editor.move_cursor_right(1),
-                        DELETE1 => state = LineState::DELETE,
+                        DELETE1 => state = LineState::Delete,
                         KEY_HOME => line_editor.move_cursor_home(),
                         KEY_END => line_editor.move_cursor_end(),
                         _ => {}
                     }
                 }
-                LineState::DELETE => {
-                    state = LineState::CHAR;
-                    match ch {
-                        DELETE2 => line_editor.delete_char_curr(),
-                        _ => {}
+                LineState::Delete => {
+                    state = LineState::Char;
+                    if ch == DELETE2 {
+                        line_editor.delete_char_curr()
                     }
                 }
             }
@@ -145,6 +145,26 @@ impl Terminal {
         println!();
         Ok(self.record_history())
     }
+
+    // read a line for an attached process. returns None on Ctrl-C
+    pub fn getline_detachable(&mut self) -> Result<Option<String>, errno::Errno> {
+        let mut line_editor = LineEditor::new();
+        loop {
+            match ffi::getch_raw()? {
+                CTRL_C => {
+                    println!();
+                    return Ok(None);
+                }
+                ENTER => {
+                    println!();
+                    return Ok(Some(line_editor.buffer));
+                }
+                BACKSPACE => line_editor.delete_char_prev(),
+                ch @ 0x20..=0x7e => line_editor.print_char(ch),
+                _ => {}
+            }
+        }
+    }
 }
 
 #[cfg(test)]
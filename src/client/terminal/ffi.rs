@@ -4,11 +4,11 @@ use nix::{
     unistd::read,
 };
 
-pub fn getch() -> Result<u8, errno::Errno> {
+fn getch_without(flags: LocalFlags) -> Result<u8, errno::Errno> {
     let oterm = tcgetattr(0)?;
     let mut term = oterm.clone();
 
-    term.local_flags &= !(LocalFlags::ICANON | LocalFlags::ECHO);
+    term.local_flags &= !flags;
     term.control_chars[VMIN as usize] = 1;
     term.control_chars[VTIME as usize] = 0;
 
@@ -16,5 +16,14 @@ pub fn getch() -> Result<u8, errno::Errno> {
     let mut c: [u8; 1] = [0];
     read(0, &mut c)?;
     tcsetattr(0, TCSANOW, &oterm)?;
-    Ok(c[0] as u8)
+    Ok(c[0])
+}
+
+pub fn getch() -> Result<u8, errno::Errno> {
+    getch_without(LocalFlags::ICANON | LocalFlags::ECHO)
+}
+
+// Ctrl-C is read as a character instead of raising SIGINT
+pub fn getch_raw() -> Result<u8, errno::Errno> {
+    getch_without(LocalFlags::ICANON | LocalFlags::ECHO | LocalFlags::ISIG)
 }
@@ -68,7 +68,7 @@ impl LineEditor {
 
     fn wipe_back(&mut self, count: usize) {
         for _ in 0..count {
-            print!("{}", ' ');
+            print!(" ");
             self.cursor += 1;
         }
     }
@@ -80,11 +80,11 @@ impl LineEditor {
 
     pub fn clear_line(&self, prompt_len: usize) {
         let total_len = self.buffer.len() + prompt_len + 1;
-        print!("{}", '\r');
+        print!("\r");
         for _ in 0..total_len {
-            print!("{}", ' ');
+            print!(" ");
         }
-        print!("{}", '\r');
+        print!("\r");
     }
 
     fn delete_char(&mut self) {
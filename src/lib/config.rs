@@ -3,11 +3,16 @@ extern crate nix;
 mod config_error;
 mod parser_ini;
 
+use super::logger::{LogFormat, LogLevel, LogSink};
 use super::process_id::ProcessId;
+use super::schedule::Schedule;
 use config_error::*;
 use nix::sys::signal::Signal;
+use regex::Regex;
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
 use std::vec::Vec;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -17,6 +22,43 @@ pub enum AutoRestart {
     Never,
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ProgramType {
+    Simple,
+    // the command starts a daemon in the background and exits
+    Forking,
+    // the command runs once, a successful exit completes it
+    Oneshot,
+}
+
+// what a scheduled run does while the previous one is still going
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Overlap {
+    Skip,
+    Queue,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StdinMode {
+    Null,
+    Pipe,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum HealthProbe {
+    Command(Vec<String>),
+    Tcp(String),
+    Http(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ReadyProbe {
+    Tcp(String),
+    File(String),
+    Log(String),
+    Notify,
+}
+
 pub struct ProcessConfig {
     pub autostart: bool,
     pub autorestart: AutoRestart,
@@ -25,6 +67,12 @@ pub struct ProcessConfig {
     pub startretries: u32,
     pub stopsignal: Signal,
     pub stopwaitsecs: u64,
+    pub critical: bool,
+    pub watchdog_secs: u64,
+    pub program_type: ProgramType,
+    pub pidfile: Option<String>,
+    pub schedule: Option<Schedule>,
+    pub schedule_overlap: Overlap,
 }
 
 impl ProcessConfig {
@@ -37,6 +85,15 @@ impl ProcessConfig {
             startretries: conf.startretries,
             stopsignal: conf.stopsignal,
             stopwaitsecs: conf.stopwaitsecs,
+            critical: conf.critical,
+            watchdog_secs: conf.watchdog_secs,
+            program_type: conf.program_type,
+            pidfile: conf
+                .pidfile
+                .as_ref()
+                .map(|p| conf.resolve_path(p).display().to_string()),
+            schedule: conf.schedule.clone(),
+            schedule_overlap: conf.schedule_overlap,
         }
     }
 }
@@ -52,6 +109,7 @@ pub struct ProgramConfig {
     pub umask: Option<u32>,
     pub user: Option<String>,
     pub environment: HashMap<String, String>,
+    pub stdin: StdinMode,
 
     pub autostart: bool,
     pub autorestart: AutoRestart,
@@ -60,6 +118,23 @@ pub struct ProgramConfig {
     pub startretries: u32,
     pub stopsignal: Signal,
     pub stopwaitsecs: u64,
+    pub critical: bool,
+
+    pub healthcheck: Option<HealthProbe>,
+    pub healthcheck_interval: u64,
+    pub healthcheck_timeout: u64,
+    pub healthcheck_retries: u32,
+
+    pub ready: Option<ReadyProbe>,
+    pub watchdog_secs: u64,
+    pub program_type: ProgramType,
+    pub pidfile: Option<String>,
+    pub schedule: Option<Schedule>,
+    pub schedule_overlap: Overlap,
+    // programs which have to be Running (Completed if oneshot) before this one autostarts
+    pub depends_on: Vec<String>,
+    // files or directories whose changes restart the processes
+    pub watch: Vec<String>,
 }
 
 impl ProgramConfig {
@@ -76,12 +151,26 @@ impl ProgramConfig {
             startretries: 3,
             stopsignal: Signal::SIGTERM,
             stopwaitsecs: 10,
+            critical: false,
+            healthcheck: None,
+            healthcheck_interval: 10,
+            healthcheck_timeout: 5,
+            healthcheck_retries: 3,
+            ready: None,
+            watchdog_secs: 0,
+            program_type: ProgramType::Simple,
+            pidfile: None,
+            schedule: None,
+            schedule_overlap: Overlap::Skip,
+            depends_on: Vec::new(),
+            watch: Vec::new(),
             stdout_logfile: String::from("/dev/null"),
             stderr_logfile: String::from("/dev/null"),
             directory: "/tmp".to_owned(),
             umask: None,
             user: None,
             environment: HashMap::new(),
+            stdin: StdinMode::Null,
         }
     }
 
@@ -112,8 +201,9 @@ impl ProgramConfig {
         Ok(map)
     }
 
-    fn parse_signal(k: &str, v: &str) -> Result<Signal, ConfigValueError> {
-        match v {
+    pub fn parse_signal(k: &str, v: &str) -> Result<Signal, ConfigValueError> {
+        match v.strip_prefix("SIG").unwrap_or(v) {
+            "HUP" => Ok(Signal::SIGHUP),
             "KILL" => Ok(Signal::SIGKILL),
             "STOP" => Ok(Signal::SIGSTOP),
             "INT" => Ok(Signal::SIGINT),
@@ -150,7 +240,7 @@ impl ProgramConfig {
 
     fn parse_umask(k: &str, v: &str) -> Result<u32, ConfigValueError> {
         let value_error = ConfigValueError::new(k, v);
-        Ok(u32::from_str_radix(v, 8).map_err(|_| value_error)?)
+        u32::from_str_radix(v, 8).map_err(|_| value_error)
     }
 
     fn parse_autorestart(k: &str, v: &str) -> Result<AutoRestart, ConfigValueError> {
@@ -162,6 +252,86 @@ impl ProgramConfig {
         }
     }
 
+    fn parse_program_type(k: &str, v: &str) -> Result<ProgramType, ConfigValueError> {
+        match v {
+            "simple" => Ok(ProgramType::Simple),
+            "forking" => Ok(ProgramType::Forking),
+            "oneshot" => Ok(ProgramType::Oneshot),
+            _ => Err(ConfigValueError::new(k, v)),
+        }
+    }
+
+    fn parse_overlap(k: &str, v: &str) -> Result<Overlap, ConfigValueError> {
+        match v {
+            "skip" => Ok(Overlap::Skip),
+            "queue" => Ok(Overlap::Queue),
+            _ => Err(ConfigValueError::new(k, v)),
+        }
+    }
+
+    fn parse_stdin(k: &str, v: &str) -> Result<StdinMode, ConfigValueError> {
+        match v {
+            "null" => Ok(StdinMode::Null),
+            "pipe" => Ok(StdinMode::Pipe),
+            _ => Err(ConfigValueError::new(k, v)),
+        }
+    }
+
+    // healthcheck_cmd, healthcheck_tcp and healthcheck_http are exclusive
+    fn parse_healthcheck(
+        k: &str,
+        v: &str,
+        prev: &Option<HealthProbe>,
+    ) -> Result<HealthProbe, ConfigValueError> {
+        let value_error = ConfigValueError::new(k, v);
+        if prev.is_some() {
+            return Err(value_error);
+        }
+        match k {
+            "healthcheck_cmd" => Ok(HealthProbe::Command(
+                v.split(' ').map(|x| x.to_owned()).collect(),
+            )),
+            "healthcheck_tcp" => match v.rsplit_once(':') {
+                Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                    Ok(HealthProbe::Tcp(v.to_owned()))
+                }
+                _ => Err(value_error),
+            },
+            "healthcheck_http" if v.starts_with("http://") => Ok(HealthProbe::Http(v.to_owned())),
+            _ => Err(value_error),
+        }
+    }
+
+    // ready_tcp, ready_file, ready_log and ready_notify are exclusive
+    fn parse_ready(
+        k: &str,
+        v: &str,
+        prev: &Option<ReadyProbe>,
+    ) -> Result<Option<ReadyProbe>, ConfigValueError> {
+        let value_error = ConfigValueError::new(k, v);
+        if prev.is_some() {
+            return Err(value_error);
+        }
+        match k {
+            "ready_tcp" => match v.rsplit_once(':') {
+                Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                    Ok(Some(ReadyProbe::Tcp(v.to_owned())))
+                }
+                _ => Err(value_error),
+            },
+            "ready_file" if !v.is_empty() => Ok(Some(ReadyProbe::File(v.to_owned()))),
+            "ready_log" => match Regex::new(v) {
+                Ok(_) => Ok(Some(ReadyProbe::Log(v.to_owned()))),
+                Err(_) => Err(value_error),
+            },
+            "ready_notify" => match ProgramConfig::parse::<bool>(k, v)? {
+                true => Ok(Some(ReadyProbe::Notify)),
+                false => Ok(None),
+            },
+            _ => Err(value_error),
+        }
+    }
+
     fn parse<T: std::str::FromStr>(k: &str, v: &str) -> Result<T, ConfigValueError> {
         let value_error = ConfigValueError::new(k, v);
         v.to_owned().parse::<T>().map_err(|_| value_error)
@@ -186,12 +356,43 @@ impl ProgramConfig {
                 "umask" => config.umask = Some(ProgramConfig::parse_umask(k, v)? % 0o777),
                 "user" => config.user = Some(v.to_owned()),
                 "environment" => config.environment = ProgramConfig::parse_environment(k, v)?,
+                "stdin" => config.stdin = ProgramConfig::parse_stdin(k, v)?,
+                "critical" => config.critical = ProgramConfig::parse::<bool>(k, v)?,
+                "healthcheck_cmd" | "healthcheck_tcp" | "healthcheck_http" => {
+                    config.healthcheck =
+                        Some(ProgramConfig::parse_healthcheck(k, v, &config.healthcheck)?)
+                }
+                "healthcheck_interval" => {
+                    config.healthcheck_interval = ProgramConfig::parse::<u64>(k, v)?
+                }
+                "healthcheck_timeout" => {
+                    config.healthcheck_timeout = ProgramConfig::parse::<u64>(k, v)?
+                }
+                "healthcheck_retries" => {
+                    config.healthcheck_retries = ProgramConfig::parse::<u32>(k, v)?
+                }
+                "ready_tcp" | "ready_file" | "ready_log" | "ready_notify" => {
+                    config.ready = ProgramConfig::parse_ready(k, v, &config.ready)?
+                }
+                "watchdog_secs" => config.watchdog_secs = ProgramConfig::parse::<u64>(k, v)?,
+                "type" => config.program_type = ProgramConfig::parse_program_type(k, v)?,
+                "pidfile" => config.pidfile = Some(v.to_owned()),
+                "schedule" => config.schedule = Some(ProgramConfig::parse::<Schedule>(k, v)?),
+                "schedule_overlap" => config.schedule_overlap = ProgramConfig::parse_overlap(k, v)?,
+                "depends_on" => {
+                    config.depends_on = v.split(',').map(|x| x.trim().to_owned()).collect()
+                }
+                "watch" => config.watch = v.split(',').map(|x| x.trim().to_owned()).collect(),
                 _ => return Err(Box::new(ConfigKeyError::new(k))),
             }
         }
-        if config.command.len() == 0 {
+        if config.command.is_empty() {
             return Err(Box::new(ConfigCommandError));
         }
+        // every instance would take the daemon of the others for its own
+        if let (Some(pidfile), true) = (config.pidfile.as_ref(), config.numprocs > 1) {
+            return Err(Box::new(ConfigValueError::new("pidfile", pidfile)));
+        }
         Ok(config)
     }
 
@@ -202,6 +403,7 @@ impl ProgramConfig {
             || self.umask != other.umask
             || self.user != other.user
             || self.environment != other.environment
+            || self.stdin != other.stdin
             || self.autostart != other.autostart
             || self.autorestart != other.autorestart
             || self.exitcodes != other.exitcodes
@@ -209,8 +411,26 @@ impl ProgramConfig {
             || self.startretries != other.startretries
             || self.stopsignal != other.stopsignal
             || self.stopwaitsecs != other.stopwaitsecs
+            || self.critical != other.critical
+            || self.healthcheck != other.healthcheck
+            || self.healthcheck_interval != other.healthcheck_interval
+            || self.healthcheck_timeout != other.healthcheck_timeout
+            || self.healthcheck_retries != other.healthcheck_retries
+            || self.ready != other.ready
+            || self.watchdog_secs != other.watchdog_secs
+            || self.program_type != other.program_type
+            || self.pidfile != other.pidfile
+            || self.schedule != other.schedule
+            || self.schedule_overlap != other.schedule_overlap
+            || self.depends_on != other.depends_on
+            || self.watch != other.watch
             || self.command != other.command
     }
+
+    // paths given for the program are relative to its directory, like its command
+    pub fn resolve_path(&self, path: &str) -> PathBuf {
+        Path::new(&self.directory).join(path)
+    }
 }
 
 impl std::fmt::Display for ProgramConfig {
@@ -222,40 +442,127 @@ impl std::fmt::Display for ProgramConfig {
 #[derive(Debug, PartialEq)]
 pub struct GeneralConfig {
     pub sockfile: String,
+    pub pidfile: String,
+    pub nodaemon: bool,
+    pub logfile: String,
+    pub loglevel: LogLevel,
+    pub logfile_maxbytes: u64,
+    pub logfile_backups: u32,
+    pub logsink: LogSink,
+    pub logformat: LogFormat,
+    pub statedir: Option<String>,
+    pub shutdown_timeout: u64,
+    pub init: bool,
+    // update when the config file changes
+    pub autoreload: bool,
+}
+
+impl Default for GeneralConfig {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GeneralConfig {
     pub fn new() -> Self {
         GeneralConfig {
             sockfile: "/tmp/taskmasterd.sock".to_owned(),
+            pidfile: "/tmp/taskmasterd.pid".to_owned(),
+            nodaemon: false,
+            logfile: crate::LOG_FILE.to_owned(),
+            loglevel: LogLevel::Info,
+            logfile_maxbytes: 50 * 1024 * 1024,
+            logfile_backups: 10,
+            logsink: LogSink::File,
+            logformat: LogFormat::Text,
+            statedir: None,
+            shutdown_timeout: 30,
+            init: false,
+            autoreload: false,
+        }
+    }
+
+    fn parse_logsink(k: &str, v: &str) -> Result<LogSink, ConfigValueError> {
+        match v {
+            "file" => Ok(LogSink::File),
+            "stdout" => Ok(LogSink::Stdout),
+            "syslog" => Ok(LogSink::Syslog),
+            _ => Err(ConfigValueError::new(k, v)),
         }
     }
 
-    pub fn from(prop: &ini::Properties) -> Result<Self, ConfigKeyError> {
+    fn parse_logformat(k: &str, v: &str) -> Result<LogFormat, ConfigValueError> {
+        match v {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(ConfigValueError::new(k, v)),
+        }
+    }
+
+    fn parse_loglevel(k: &str, v: &str) -> Result<LogLevel, ConfigValueError> {
+        match v {
+            "critical" => Ok(LogLevel::Crit),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            "trace" => Ok(LogLevel::Trace),
+            _ => Err(ConfigValueError::new(k, v)),
+        }
+    }
+
+    // 1024, 1KB, 50MB, 1GB
+    fn parse_bytes(k: &str, v: &str) -> Result<u64, ConfigValueError> {
+        let units = [
+            ("KB", 1024),
+            ("MB", 1024 * 1024),
+            ("GB", 1024 * 1024 * 1024),
+        ];
+        let (number, unit) = units
+            .iter()
+            .find_map(|(suffix, unit)| Some((v.strip_suffix(suffix)?, *unit)))
+            .unwrap_or((v, 1));
+        ProgramConfig::parse::<u64>(k, number)
+            .ok()
+            .and_then(|n| n.checked_mul(unit))
+            .ok_or_else(|| ConfigValueError::new(k, v))
+    }
+
+    pub fn from(prop: &ini::Properties) -> Result<Self, Box<dyn Error>> {
         let mut config = GeneralConfig::new();
         for (k, v) in prop.iter() {
             match k {
                 "sockfile" => config.sockfile = v.to_owned(),
-                _ => return Err(ConfigKeyError::new(k)),
+                "pidfile" => config.pidfile = v.to_owned(),
+                "nodaemon" => config.nodaemon = ProgramConfig::parse::<bool>(k, v)?,
+                "logfile" => config.logfile = v.to_owned(),
+                "loglevel" => config.loglevel = GeneralConfig::parse_loglevel(k, v)?,
+                "logfile_maxbytes" => config.logfile_maxbytes = GeneralConfig::parse_bytes(k, v)?,
+                "logfile_backups" => config.logfile_backups = ProgramConfig::parse::<u32>(k, v)?,
+                "logsink" => config.logsink = GeneralConfig::parse_logsink(k, v)?,
+                "logformat" => config.logformat = GeneralConfig::parse_logformat(k, v)?,
+                "statedir" => config.statedir = Some(v.to_owned()),
+                "shutdown_timeout" => config.shutdown_timeout = ProgramConfig::parse::<u64>(k, v)?,
+                "init" => config.init = ProgramConfig::parse::<bool>(k, v)?,
+                "autoreload" => config.autoreload = ProgramConfig::parse::<bool>(k, v)?,
+                _ => return Err(Box::new(ConfigKeyError::new(k))),
             }
         }
         Ok(config)
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Default)]
 pub struct Config {
     pub general: GeneralConfig,
     pub programs: HashMap<String, ProgramConfig>,
+    // files read through [include], and the directories their patterns were matched in
+    pub includes: Vec<PathBuf>,
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Config {
-            general: GeneralConfig::new(),
-            programs: Default::default(),
-        }
-    }
+// how far check_dependencies got with a program
+enum Mark {
+    Visiting,
+    Visited,
 }
 
 impl Config {
@@ -263,29 +570,133 @@ impl Config {
         let ini = parser_ini::load_ini(file_path)?;
         let mut general = GeneralConfig::new();
         let mut programs = HashMap::new();
+        let mut files = Vec::new();
+        let mut dirs = Vec::new();
         for (sec, prop) in ini.iter() {
             match sec {
                 None => {}
                 Some("general") => general = GeneralConfig::from(prop)?,
-                Some(sec) => {
-                    if let Some((key, value)) = sec.split_once(":") {
-                        if let "program" = key {
-                            programs.insert(value.to_owned(), ProgramConfig::from(value, prop)?);
-                        }
-                    }
+                Some("include") => (files, dirs) = Config::parse_include(file_path, prop)?,
+                Some(sec) => Config::add_program(&mut programs, sec, prop)?,
+            }
+        }
+        // included files only add programs
+        for file in files.iter() {
+            let ini = parser_ini::load_ini(&file.display().to_string())?;
+            for (sec, prop) in ini.iter() {
+                if let Some(sec) = sec {
+                    Config::add_program(&mut programs, sec, prop)?;
                 }
             }
         }
-        Ok(Config { general, programs })
+        Config::check_dependencies(&programs)?;
+        files.extend(dirs);
+        Ok(Config {
+            general,
+            programs,
+            includes: files,
+        })
+    }
+
+    fn add_program(
+        programs: &mut HashMap<String, ProgramConfig>,
+        sec: &str,
+        prop: &ini::Properties,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(("program", name)) = sec.split_once(":") {
+            // a program defined again, e.g. in an included file
+            if programs.contains_key(name) {
+                return Err(Box::new(ConfigValueError::new("program", name)));
+            }
+            programs.insert(name.to_owned(), ProgramConfig::from(name, prop)?);
+        }
+        Ok(())
+    }
+
+    // files = a list of files relative to the including one. a * in the file name
+    // stands for any part of it, e.g. conf.d/*.ini. returns the files and the pattern dirs
+    fn parse_include(
+        file_path: &str,
+        prop: &ini::Properties,
+    ) -> Result<(Vec<PathBuf>, Vec<PathBuf>), Box<dyn Error>> {
+        let base = Path::new(file_path).parent().unwrap_or(Path::new(""));
+        let mut files = Vec::new();
+        let mut dirs = Vec::new();
+        for (k, v) in prop.iter() {
+            if k != "files" {
+                return Err(Box::new(ConfigKeyError::new(k)));
+            }
+            for entry in v.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+                let path = base.join(entry);
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                let Some((prefix, suffix)) = name.split_once('*') else {
+                    files.push(path);
+                    continue;
+                };
+                let dir = match path.parent() {
+                    Some(p) if p != Path::new("") => p.to_owned(),
+                    _ => PathBuf::from("."),
+                };
+                let mut matched: Vec<PathBuf> = read_dir(&dir)
+                    .map_err(|_| ConfigValueError::new(k, entry))?
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| {
+                        p.file_name().and_then(|n| n.to_str()).is_some_and(|n| {
+                            n.len() >= prefix.len() + suffix.len()
+                                && n.starts_with(prefix)
+                                && n.ends_with(suffix)
+                        })
+                    })
+                    .collect();
+                matched.sort();
+                files.extend(matched);
+                dirs.push(dir);
+            }
+        }
+        Ok((files, dirs))
+    }
+
+    // every dependency exists and none leads back to the program, which would never start
+    fn check_dependencies(
+        programs: &HashMap<String, ProgramConfig>,
+    ) -> Result<(), ConfigValueError> {
+        let mut marks = HashMap::new();
+        for name in programs.keys() {
+            Config::visit_dependencies(programs, name, &mut marks)?;
+        }
+        Ok(())
+    }
+
+    // depth first, each program once. a dependency still being visited closes a cycle
+    fn visit_dependencies<'a>(
+        programs: &'a HashMap<String, ProgramConfig>,
+        name: &'a str,
+        marks: &mut HashMap<&'a str, Mark>,
+    ) -> Result<(), ConfigValueError> {
+        if marks.contains_key(name) {
+            return Ok(());
+        }
+        marks.insert(name, Mark::Visiting);
+        for dep in programs[name].depends_on.iter() {
+            match marks.get(dep.as_str()) {
+                Some(Mark::Visited) => {}
+                Some(Mark::Visiting) => return Err(ConfigValueError::new("depends_on", dep)),
+                None if !programs.contains_key(dep) => {
+                    return Err(ConfigValueError::new("depends_on", dep))
+                }
+                None => Config::visit_dependencies(programs, dep, marks)?,
+            }
+        }
+        marks.insert(name, Mark::Visited);
+        Ok(())
     }
 
     pub fn process_list(&self) -> HashSet<ProcessId> {
         let mut set = HashSet::new();
         self.programs.iter().for_each(|(k, v)| {
             for process_num in 0..v.numprocs {
-                match set.insert(ProcessId::new(k.to_owned(), process_num)) {
-                    _ => {}
-                }
+                set.insert(ProcessId::new(k.to_owned(), process_num));
             }
         });
         set
@@ -299,10 +710,9 @@ mod tests {
     #[test]
     fn test_empty() {
         let expected: Config = Config {
-            general: GeneralConfig {
-                sockfile: "/tmp/taskmasterd.sock".to_owned(),
-            },
+            general: GeneralConfig::new(),
             programs: Default::default(),
+            includes: Vec::new(),
         };
         let c = Config::from("./src/lib/config/test/general_no_option.ini");
         assert_eq!(expected, c.unwrap());
@@ -313,8 +723,21 @@ mod tests {
         let expected: Config = Config {
             general: GeneralConfig {
                 sockfile: "/tmp/test.general.sock".to_owned(),
+                pidfile: "/tmp/test.general.pid".to_owned(),
+                nodaemon: true,
+                logfile: "/tmp/test.general.log".to_owned(),
+                loglevel: LogLevel::Debug,
+                logfile_maxbytes: 1024 * 1024,
+                logfile_backups: 3,
+                logsink: LogSink::Stdout,
+                logformat: LogFormat::Json,
+                statedir: Some("/tmp/test.general.state".to_owned()),
+                shutdown_timeout: 5,
+                init: true,
+                autoreload: true,
             },
             programs: Default::default(),
+            includes: Vec::new(),
         };
         let c = Config::from("./src/lib/config/test/general.ini");
         assert_eq!(expected, c.unwrap());
@@ -323,10 +746,9 @@ mod tests {
     #[test]
     fn test_general_no_option() {
         let expected: Config = Config {
-            general: GeneralConfig {
-                sockfile: "/tmp/taskmasterd.sock".to_owned(),
-            },
+            general: GeneralConfig::new(),
             programs: Default::default(),
+            includes: Vec::new(),
         };
         let c = Config::from("./src/lib/config/test/general_no_option.ini");
         assert_eq!(expected, c.unwrap());
@@ -341,6 +763,51 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_general_invalid_value_loglevel() {
+        let c = Config::from("./src/lib/config/test/general_invalid_value_loglevel.ini");
+        assert_eq!(
+            "configuration: invalid value: loglevel: verbose",
+            c.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_general_invalid_value_logsink() {
+        let c = Config::from("./src/lib/config/test/general_invalid_value_logsink.ini");
+        assert_eq!(
+            "configuration: invalid value: logsink: journal",
+            c.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_general_invalid_value_bytes() {
+        let c = Config::from("./src/lib/config/test/general_invalid_value_bytes.ini");
+        assert_eq!(
+            "configuration: invalid value: logfile_maxbytes: 10TB",
+            c.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_general_invalid_value_bytes_unit() {
+        let c = Config::from("./src/lib/config/test/general_invalid_value_bytes_unit.ini");
+        assert_eq!(
+            "configuration: invalid value: logfile_maxbytes: 1€",
+            c.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_general_invalid_value_bytes_overflow() {
+        let c = Config::from("./src/lib/config/test/general_invalid_value_bytes_overflow.ini");
+        assert_eq!(
+            "configuration: invalid value: logfile_maxbytes: 18446744073709551615GB",
+            c.unwrap_err().to_string()
+        );
+    }
+
     #[test]
     fn test_program_invalid_value_u32() {
         let c = Config::from("./src/lib/config/test/program_invalid_value_u32.ini");
@@ -394,6 +861,12 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_program_stopsignal_prefix() {
+        let c = Config::from("./src/lib/config/test/program_stopsignal_prefix.ini").unwrap();
+        assert_eq!(Signal::SIGHUP, c.programs.get("a").unwrap().stopsignal);
+    }
+
     #[test]
     fn test_program_invalid_value_autorestart() {
         let c = Config::from("./src/lib/config/test/program_invalid_value_autorestart.ini");
@@ -403,6 +876,173 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_program_healthcheck() {
+        let c = Config::from("./src/lib/config/test/program_healthcheck.ini").unwrap();
+        let a = c.programs.get("a").unwrap();
+        assert_eq!(
+            Some(HealthProbe::Http("http://localhost:8080/health".to_owned())),
+            a.healthcheck
+        );
+        assert_eq!(2, a.healthcheck_interval);
+        assert_eq!(1, a.healthcheck_timeout);
+        assert_eq!(5, a.healthcheck_retries);
+    }
+
+    #[test]
+    fn test_program_invalid_value_healthcheck() {
+        let c = Config::from("./src/lib/config/test/program_invalid_value_healthcheck.ini");
+        assert_eq!(
+            "configuration: invalid value: healthcheck_tcp: localhost:8080",
+            c.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_program_ready() {
+        let c = Config::from("./src/lib/config/test/program_ready.ini").unwrap();
+        let a = c.programs.get("a").unwrap();
+        assert_eq!(
+            Some(ReadyProbe::Log("^listening on [0-9]+$".to_owned())),
+            a.ready
+        );
+        let b = c.programs.get("b").unwrap();
+        assert_eq!(Some(ReadyProbe::Notify), b.ready);
+        assert_eq!(30, b.watchdog_secs);
+    }
+
+    #[test]
+    fn test_program_invalid_value_ready() {
+        let c = Config::from("./src/lib/config/test/program_invalid_value_ready.ini");
+        assert_eq!(
+            "configuration: invalid value: ready_log: (unclosed",
+            c.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_program_forking() {
+        let c = Config::from("./src/lib/config/test/program_forking.ini").unwrap();
+        let a = c.programs.get("a").unwrap();
+        assert_eq!(ProgramType::Forking, a.program_type);
+        assert_eq!(Some("run/a.pid".to_owned()), a.pidfile);
+        let b = c.programs.get("b").unwrap();
+        assert_eq!(ProgramType::Simple, b.program_type);
+        assert_eq!(None, b.pidfile);
+    }
+
+    #[test]
+    fn test_program_pidfile_numprocs() {
+        let c = Config::from("./src/lib/config/test/program_pidfile_numprocs.ini");
+        assert_eq!(
+            "configuration: invalid value: pidfile: run/a.pid",
+            c.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_program_invalid_value_type() {
+        let c = Config::from("./src/lib/config/test/program_invalid_value_type.ini");
+        assert_eq!(
+            "configuration: invalid value: type: daemon",
+            c.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_program_schedule() {
+        let c = Config::from("./src/lib/config/test/program_schedule.ini").unwrap();
+        let a = c.programs.get("a").unwrap();
+        assert_eq!(Some("*/5 * * * *".parse().unwrap()), a.schedule);
+        assert_eq!(Overlap::Queue, a.schedule_overlap);
+    }
+
+    #[test]
+    fn test_program_invalid_value_schedule() {
+        let c = Config::from("./src/lib/config/test/program_invalid_value_schedule.ini");
+        assert_eq!(
+            "configuration: invalid value: schedule: */5 * * *",
+            c.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_program_oneshot() {
+        let c = Config::from("./src/lib/config/test/program_oneshot.ini").unwrap();
+        let a = c.programs.get("migrate").unwrap();
+        assert_eq!(ProgramType::Oneshot, a.program_type);
+        let b = c.programs.get("web").unwrap();
+        assert_eq!(vec!["migrate".to_owned(), "cache".to_owned()], b.depends_on);
+    }
+
+    #[test]
+    fn test_program_watch() {
+        let c = Config::from("./src/lib/config/test/program_watch.ini").unwrap();
+        let a = c.programs.get("a").unwrap();
+        assert_eq!(vec!["bin/server".to_owned(), "/etc/a".to_owned()], a.watch);
+        let b = c.programs.get("b").unwrap();
+        assert!(b.watch.is_empty());
+    }
+
+    #[test]
+    fn test_program_invalid_value_depends_on() {
+        let c = Config::from("./src/lib/config/test/program_invalid_value_depends_on.ini");
+        assert_eq!(
+            "configuration: invalid value: depends_on: db",
+            c.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_program_depends_on_cycle() {
+        let c = Config::from("./src/lib/config/test/program_depends_on_cycle.ini");
+        assert!(c
+            .unwrap_err()
+            .to_string()
+            .starts_with("configuration: invalid value: depends_on: "));
+    }
+
+    #[test]
+    fn test_program_depends_on_diamonds() {
+        let c = Config::from("./src/lib/config/test/program_depends_on_diamonds.ini");
+        assert_eq!(61, c.unwrap().programs.len());
+    }
+
+    #[test]
+    fn test_include() {
+        let c = Config::from("./src/lib/config/test/include.ini").unwrap();
+        let mut names: Vec<&str> = c.programs.keys().map(|k| k.as_str()).collect();
+        names.sort();
+        assert_eq!(vec!["a", "b", "main", "web"], names);
+        assert_eq!(
+            vec![
+                PathBuf::from("./src/lib/config/test/include/web.ini"),
+                PathBuf::from("./src/lib/config/test/include/conf.d/a.ini"),
+                PathBuf::from("./src/lib/config/test/include/conf.d/b.ini"),
+                PathBuf::from("./src/lib/config/test/include/conf.d"),
+            ],
+            c.includes
+        );
+    }
+
+    #[test]
+    fn test_include_duplicate() {
+        let c = Config::from("./src/lib/config/test/include_duplicate.ini");
+        assert_eq!(
+            "configuration: invalid value: program: web",
+            c.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_program_invalid_value_stdin() {
+        let c = Config::from("./src/lib/config/test/program_invalid_value_stdin.ini");
+        assert_eq!(
+            "configuration: invalid value: stdin: tty",
+            c.unwrap_err().to_string()
+        );
+    }
+
     #[test]
     fn test_program_invalid_key() {
         let c = Config::from("./src/lib/config/test/program_invalid_key.ini");
@@ -426,6 +1066,7 @@ mod tests {
         let mut expected = Config {
             general: GeneralConfig::new(),
             programs: HashMap::new(),
+            includes: Vec::new(),
         };
 
         expected
@@ -449,6 +1090,8 @@ mod tests {
         program_config.autostart = false;
         program_config.autorestart = AutoRestart::Never;
         program_config.stopsignal = Signal::SIGKILL;
+        program_config.stdin = StdinMode::Pipe;
+        program_config.critical = true;
 
         let c = Config::from("./src/lib/config/test/program.ini");
         assert_eq!(expected, c.unwrap())
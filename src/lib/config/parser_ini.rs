@@ -21,8 +21,7 @@ mod tests {
 
     #[test]
     fn test_load_ini() -> Result<(), ConfigFileError> {
-        let ini = load_ini("conf.ini")?;
-        print!("{ini}");
+        load_ini("conf.ini")?;
         Ok(())
     }
 }
@@ -1,22 +1,32 @@
 use libc;
 use nix::{
     errno::Errno,
+    fcntl::{flock, FlockArg},
     sys::stat::Mode,
-    unistd::{close, dup2, fork, setsid, ForkResult},
+    unistd::{close, dup2, fork, getpid, setsid, ForkResult},
 };
 
 use nix::fcntl::{open, OFlag};
 
-fn replace_std_fd(filename: &str) -> Result<(), Errno> {
+use std::error::Error;
+use std::fs::{remove_file, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::Mutex;
+
+// the logfile stdout and stderr of the daemon go to, opened again on reopen
+static STD_LOGFILE: Mutex<Option<String>> = Mutex::new(None);
+
+fn replace_std_fd(filename: &str, std_fds: &[RawFd]) -> Result<(), Errno> {
     let fd = open(
         filename,
         OFlag::O_RDWR | OFlag::O_APPEND | OFlag::O_CREAT,
         Mode::from_bits(0o600).expect("hardcoded mode"),
     )?;
 
-    dup2(fd, 0)?;
-    dup2(fd, 1)?;
-    dup2(fd, 2)?;
+    for std_fd in std_fds {
+        dup2(fd, *std_fd)?;
+    }
 
     close(fd)
 }
@@ -29,5 +39,79 @@ pub fn daemonize(logfile: &str) -> Result<(), Errno> {
     }
 
     setsid()?;
-    replace_std_fd(logfile)
+    replace_std_fd(logfile, &[0, 1, 2])?;
+    keep_std_logfile(logfile);
+    Ok(())
+}
+
+// for a taskmasterd which inherited the std fds of a daemon, e.g. after reexec
+pub fn keep_std_logfile(logfile: &str) {
+    *STD_LOGFILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(logfile.to_owned());
+}
+
+// stdout and stderr would still write to a logfile which has been moved away
+pub fn reopen_std_logfile() -> Result<(), Errno> {
+    match STD_LOGFILE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+    {
+        Some(logfile) => replace_std_fd(logfile, &[1, 2]),
+        None => Ok(()),
+    }
+}
+
+#[derive(Debug)]
+pub struct AlreadyRunningError(String);
+
+impl std::fmt::Display for AlreadyRunningError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "another taskmasterd is already running (pid {})", self.0)
+    }
+}
+
+impl Error for AlreadyRunningError {}
+
+// holds an exclusive lock on the pidfile while taskmasterd is alive.
+// the lock is shared with the forked daemon and released when it exits.
+pub struct PidFile {
+    path: String,
+    file: File,
+}
+
+impl PidFile {
+    pub fn lock(path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        if let Err(e) = flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock) {
+            if e != Errno::EWOULDBLOCK {
+                return Err(Box::new(e));
+            }
+            let mut pid = String::new();
+            file.read_to_string(&mut pid)?;
+            return Err(Box::new(AlreadyRunningError(pid.trim().to_owned())));
+        }
+
+        Ok(PidFile {
+            path: path.to_owned(),
+            file,
+        })
+    }
+
+    pub fn write_pid(&mut self) -> std::io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        writeln!(self.file, "{}", getpid())
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        remove_file(&self.path).unwrap_or_default();
+    }
 }
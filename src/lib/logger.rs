@@ -1,13 +1,25 @@
 use libc::{localtime_r, strftime, tm};
+use serde_json::{Map, Value};
 use std::fmt;
+use std::fs::{rename, File, OpenOptions};
+use std::io::{self, Write};
 use std::mem::MaybeUninit;
+use std::os::unix::net::UnixDatagram;
+use std::sync::{Mutex, MutexGuard};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(PartialEq, PartialOrd)]
+use crate::config::GeneralConfig;
+
+const SYSLOG_SOCKET: &str = "/dev/log";
+const SYSLOG_FACILITY_DAEMON: u8 = 3;
+
+#[derive(PartialEq, PartialOrd, Debug, Clone, Copy)]
 pub enum LogLevel {
     Crit = 0,
     Warn = 1,
     Info = 2,
+    Debug = 3,
+    Trace = 4,
 }
 
 impl fmt::Display for LogLevel {
@@ -16,6 +28,8 @@ impl fmt::Display for LogLevel {
             f,
             "{}",
             match self {
+                LogLevel::Trace => "TRAC",
+                LogLevel::Debug => "DEBG",
                 LogLevel::Info => "INFO",
                 LogLevel::Warn => "WARN",
                 LogLevel::Crit => "CRIT",
@@ -24,9 +38,187 @@ impl fmt::Display for LogLevel {
     }
 }
 
-pub struct Logger(pub LogLevel);
+impl LogLevel {
+    fn syslog_severity(&self) -> u8 {
+        match self {
+            LogLevel::Crit => 2,
+            LogLevel::Warn => 4,
+            LogLevel::Info => 6,
+            LogLevel::Debug | LogLevel::Trace => 7,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum LogSink {
+    File,
+    Stdout,
+    Syslog,
+}
+
+pub type Fields<'a> = &'a [(&'a str, Value)];
+
+// where formatted log lines go
+trait Sink: Send {
+    fn write(&mut self, level: LogLevel, line: &str) -> io::Result<()>;
+
+    // called after the log has been moved away (e.g. by logrotate)
+    fn reopen(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct StdoutSink;
+
+impl Sink for StdoutSink {
+    fn write(&mut self, _: LogLevel, line: &str) -> io::Result<()> {
+        io::stdout().write_all(line.as_bytes())
+    }
+}
+
+struct SyslogSink {
+    socket: UnixDatagram,
+}
+
+impl SyslogSink {
+    fn open() -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(SYSLOG_SOCKET)?;
+        Ok(SyslogSink { socket })
+    }
+}
+
+impl Sink for SyslogSink {
+    fn write(&mut self, level: LogLevel, line: &str) -> io::Result<()> {
+        let priority = SYSLOG_FACILITY_DAEMON * 8 + level.syslog_severity();
+        let message = format!(
+            "<{priority}>taskmasterd[{}]: {}",
+            std::process::id(),
+            line.trim_end()
+        );
+        if self.socket.send(message.as_bytes()).is_err() {
+            // syslog daemon may have been restarted
+            self.socket.connect(SYSLOG_SOCKET)?;
+            self.socket.send(message.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        *self = SyslogSink::open()?;
+        Ok(())
+    }
+}
+
+struct LogFile {
+    path: String,
+    file: File,
+    size: u64,
+    maxbytes: u64,
+    backups: u32,
+}
+
+impl LogFile {
+    fn open(path: &str, maxbytes: u64, backups: u32) -> io::Result<Self> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(LogFile {
+            path: path.to_owned(),
+            file,
+            size,
+            maxbytes,
+            backups,
+        })
+    }
+
+    // taskmaster.log -> taskmaster.log.1 -> ... -> taskmaster.log.{backups}
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.backups == 0 {
+            self.file.set_len(0)?;
+        } else {
+            for i in (1..self.backups).rev() {
+                let from = format!("{}.{}", self.path, i);
+                rename(&from, format!("{}.{}", self.path, i + 1)).unwrap_or_default();
+            }
+            rename(&self.path, format!("{}.1", self.path))?;
+            self.file = OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Sink for LogFile {
+    fn write(&mut self, _: LogLevel, line: &str) -> io::Result<()> {
+        if self.maxbytes != 0 && self.size + line.len() as u64 > self.maxbytes {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        *self = LogFile::open(&self.path, self.maxbytes, self.backups)?;
+        Ok(())
+    }
+}
+
+struct LoggerInner {
+    level: LogLevel,
+    format: LogFormat,
+    sink: Option<Box<dyn Sink>>,
+}
+
+pub struct Logger(Mutex<LoggerInner>);
 
 impl Logger {
+    pub const fn new(level: LogLevel) -> Self {
+        Logger(Mutex::new(LoggerInner {
+            level,
+            format: LogFormat::Text,
+            sink: None,
+        }))
+    }
+
+    fn inner(&self) -> MutexGuard<'_, LoggerInner> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // can be called again at runtime to apply a new configuration
+    pub fn configure(&self, conf: &GeneralConfig) -> io::Result<()> {
+        let sink: Box<dyn Sink> = match conf.logsink {
+            LogSink::File => Box::new(LogFile::open(
+                &conf.logfile,
+                conf.logfile_maxbytes,
+                conf.logfile_backups,
+            )?),
+            LogSink::Stdout => Box::new(StdoutSink),
+            LogSink::Syslog => Box::new(SyslogSink::open()?),
+        };
+        let mut inner = self.inner();
+        inner.level = conf.loglevel;
+        inner.format = conf.logformat;
+        inner.sink = Some(sink);
+        Ok(())
+    }
+
+    pub fn reopen(&self) -> io::Result<()> {
+        match self.inner().sink.as_mut() {
+            Some(sink) => sink.reopen(),
+            None => Ok(()),
+        }
+    }
+
     fn get_epoch_time() -> (i64, i64) {
         let now = SystemTime::now();
         let since_the_epoch = now.duration_since(UNIX_EPOCH).expect("Time went backwards");
@@ -47,7 +239,7 @@ impl Logger {
             strftime(
                 buf.as_mut_ptr() as *mut i8,
                 64,
-                "%Y-%m-%d %H:%M:%S.\0".as_ptr() as *const i8,
+                c"%Y-%m-%d %H:%M:%S.".as_ptr(),
                 &datetime,
             )
         };
@@ -62,12 +254,60 @@ impl Logger {
         timestamp
     }
 
-    fn log(&self, level: LogLevel, message: &str) {
-        if self.0 >= level {
-            println!("{} {level} {message}", Self::get_formated_timestamp());
+    fn format(format: LogFormat, level: LogLevel, message: &str, fields: Fields) -> String {
+        let timestamp = Self::get_formated_timestamp();
+        match format {
+            LogFormat::Text => {
+                let mut line = format!("{timestamp} {level} {message}");
+                for (k, v) in fields.iter().filter(|(_, v)| !v.is_null()) {
+                    match v {
+                        Value::String(s) => line.push_str(&format!(" {k}={s}")),
+                        _ => line.push_str(&format!(" {k}={v}")),
+                    }
+                }
+                line + "\n"
+            }
+            LogFormat::Json => {
+                let mut record = Map::new();
+                record.insert("timestamp".to_owned(), Value::from(timestamp));
+                record.insert("level".to_owned(), Value::from(level.to_string()));
+                record.insert("message".to_owned(), Value::from(message));
+                for (k, v) in fields.iter() {
+                    record.insert(k.to_string(), v.clone());
+                }
+                Value::Object(record).to_string() + "\n"
+            }
+        }
+    }
+
+    pub fn log_with(&self, level: LogLevel, message: &str, fields: Fields) {
+        let mut inner = self.inner();
+        if inner.level < level {
+            return;
+        }
+
+        let line = Self::format(inner.format, level, message, fields);
+        let written = match inner.sink.as_mut() {
+            Some(sink) => sink.write(level, &line),
+            None => StdoutSink.write(level, &line),
+        };
+        if let Err(e) = written {
+            eprint!("log write failed - {e}: {line}");
         }
     }
 
+    fn log(&self, level: LogLevel, message: &str) {
+        self.log_with(level, message, &[]);
+    }
+
+    pub fn trace(&self, message: &str) {
+        self.log(LogLevel::Trace, message);
+    }
+
+    pub fn debug(&self, message: &str) {
+        self.log(LogLevel::Debug, message);
+    }
+
     pub fn info(&self, message: &str) {
         self.log(LogLevel::Info, message);
     }
@@ -81,4 +321,65 @@ impl Logger {
     }
 }
 
-pub const LOG: Logger = Logger(LogLevel::Info);
+pub static LOG: Logger = Logger::new(LogLevel::Info);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{read_to_string, remove_file};
+
+    #[test]
+    fn test_rotate() {
+        let path = "/tmp/taskmaster.test_rotate.log";
+        for p in [path, "/tmp/taskmaster.test_rotate.log.1"] {
+            remove_file(p).unwrap_or_default();
+        }
+
+        let mut file = LogFile::open(path, 10, 1).unwrap();
+        file.write(LogLevel::Info, "12345678\n").unwrap();
+        file.write(LogLevel::Info, "abcdefgh\n").unwrap();
+
+        assert_eq!("abcdefgh\n", read_to_string(path).unwrap());
+        assert_eq!(
+            "12345678\n",
+            read_to_string("/tmp/taskmaster.test_rotate.log.1").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_reopen() {
+        let path = "/tmp/taskmaster.test_reopen.log";
+        let moved = "/tmp/taskmaster.test_reopen.log.moved";
+        remove_file(path).unwrap_or_default();
+
+        let mut file = LogFile::open(path, 0, 0).unwrap();
+        file.write(LogLevel::Info, "before\n").unwrap();
+        rename(path, moved).unwrap();
+        file.reopen().unwrap();
+        file.write(LogLevel::Info, "after\n").unwrap();
+
+        assert_eq!("before\n", read_to_string(moved).unwrap());
+        assert_eq!("after\n", read_to_string(path).unwrap());
+        remove_file(moved).unwrap_or_default();
+    }
+
+    #[test]
+    fn test_format() {
+        let fields: Fields = &[
+            ("program", Value::from("a")),
+            ("seq", Value::from(0)),
+            ("pid", Value::Null),
+        ];
+
+        let text = Logger::format(LogFormat::Text, LogLevel::Info, "hello", fields);
+        assert!(text.ends_with(" INFO hello program=a seq=0\n"));
+
+        let json = Logger::format(LogFormat::Json, LogLevel::Warn, "hello", fields);
+        let json: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!("WARN", json["level"]);
+        assert_eq!("hello", json["message"]);
+        assert_eq!("a", json["program"]);
+        assert_eq!(0, json["seq"]);
+        assert!(json["pid"].is_null());
+    }
+}
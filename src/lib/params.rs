@@ -0,0 +1,134 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::config::ProgramConfig;
+use crate::process_status::ProcessState;
+use crate::response::Error;
+
+// what a method takes, checked by the server once deserialized.
+// names are "name:seq", "name:*" or "all", the supervisor resolves them
+pub trait Params: DeserializeOwned {
+    fn validate(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+// reload, shutdown, update, reopenlogs, reexec
+impl Params for () {}
+
+fn positive(key: &str, n: Option<u64>) -> Result<(), Error> {
+    match n {
+        Some(0) => Err(Error::invalid_request(key)),
+        _ => Ok(()),
+    }
+}
+
+// status: every process if `names` is empty
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct Targets {
+    pub names: Vec<String>,
+}
+
+impl Params for Targets {}
+
+// start, stop
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct ControlParams {
+    pub names: Vec<String>,
+    #[serde(default)]
+    pub wait: bool,
+    pub timeout: Option<u64>,
+}
+
+impl Params for ControlParams {
+    fn validate(&self) -> Result<(), Error> {
+        positive("timeout", self.timeout)?;
+        if self.timeout.is_some() && !self.wait {
+            return Err(Error::invalid_request("argument"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct RestartParams {
+    pub names: Vec<String>,
+    #[serde(default)]
+    pub wait: bool,
+    pub timeout: Option<u64>,
+    #[serde(default)]
+    pub rolling: bool,
+    pub batch: Option<u64>,
+    #[serde(default)]
+    pub wait_running: bool,
+}
+
+impl Params for RestartParams {
+    // a rolling restart waits batch by batch, the others answer once restarted
+    fn validate(&self) -> Result<(), Error> {
+        positive("timeout", self.timeout)?;
+        positive("batch", self.batch)?;
+        if (self.timeout.is_some() && !self.wait)
+            || (self.rolling && self.wait)
+            || (!self.rolling && (self.batch.is_some() || self.wait_running))
+        {
+            return Err(Error::invalid_request("argument"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SignalParams {
+    pub signal: String,
+    pub names: Vec<String>,
+}
+
+impl Params for SignalParams {
+    fn validate(&self) -> Result<(), Error> {
+        ProgramConfig::parse_signal("signal", &self.signal)
+            .map_err(|_| Error::invalid_request("signal"))?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SendStdinParams {
+    pub name: String,
+    pub text: String,
+}
+
+impl Params for SendStdinParams {}
+
+// watch pause|resume
+#[derive(Deserialize, Serialize, Debug)]
+pub struct WatchParams {
+    pub paused: bool,
+    pub names: Vec<String>,
+}
+
+impl Params for WatchParams {}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct WaitParams {
+    pub state: ProcessState,
+    pub names: Vec<String>,
+    pub timeout: Option<u64>,
+}
+
+impl Params for WaitParams {
+    fn validate(&self) -> Result<(), Error> {
+        positive("timeout", self.timeout)?;
+        if self.names.is_empty() {
+            return Err(Error::invalid_request("argument"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct FgParams {
+    pub name: String,
+}
+
+impl Params for FgParams {}
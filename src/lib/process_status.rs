@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(PartialEq, Clone, Copy, Deserialize, Serialize, Debug)]
@@ -5,9 +7,12 @@ pub enum ProcessState {
     Stopped,
     Starting,
     Running,
+    Unhealthy,
     Backoff,
     Stopping,
     Exited,
+    // a oneshot program which exited successfully
+    Completed,
     Fatal,
     Unknown,
 }
@@ -18,26 +23,58 @@ impl ProcessState {
             && *self != ProcessState::Stopping
             && *self != ProcessState::Fatal
             && *self != ProcessState::Exited
+            && *self != ProcessState::Completed
+    }
+
+    pub fn alive(&self) -> bool {
+        *self == ProcessState::Starting
+            || *self == ProcessState::Running
+            || *self == ProcessState::Unhealthy
+            || *self == ProcessState::Stopping
     }
 
     pub fn startable(&self) -> bool {
         *self != ProcessState::Starting
             && *self != ProcessState::Backoff
             && *self != ProcessState::Running
+            && *self != ProcessState::Unhealthy
     }
 }
 
-impl ToString for ProcessState {
-    fn to_string(&self) -> String {
-        match self {
-            Self::Stopped => format!("Stopped"),
-            Self::Starting => format!("Starting"),
-            Self::Running => format!("Running"),
-            Self::Backoff => format!("Backoff"),
-            Self::Stopping => format!("Stopping"),
-            Self::Exited => format!("Exited"),
-            Self::Fatal => format!("Fatal"),
-            Self::Unknown => format!("Unknown"),
+impl std::fmt::Display for ProcessState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match self {
+            Self::Stopped => "Stopped",
+            Self::Starting => "Starting",
+            Self::Running => "Running",
+            Self::Unhealthy => "Unhealthy",
+            Self::Backoff => "Backoff",
+            Self::Stopping => "Stopping",
+            Self::Exited => "Exited",
+            Self::Completed => "Completed",
+            Self::Fatal => "Fatal",
+            Self::Unknown => "Unknown",
+        };
+        write!(f, "{state}")
+    }
+}
+
+// a state name as status shows it, in any case
+impl FromStr for ProcessState {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "stopped" => Ok(Self::Stopped),
+            "starting" => Ok(Self::Starting),
+            "running" => Ok(Self::Running),
+            "unhealthy" => Ok(Self::Unhealthy),
+            "backoff" => Ok(Self::Backoff),
+            "stopping" => Ok(Self::Stopping),
+            "exited" => Ok(Self::Exited),
+            "completed" => Ok(Self::Completed),
+            "fatal" => Ok(Self::Fatal),
+            _ => Err(()),
         }
     }
 }
@@ -48,6 +85,11 @@ pub struct ProcessStatus {
     seq: u32,
     state: ProcessState,
     description: String,
+    // the last STATUS= a process sent through its notify socket
+    notify_status: Option<String>,
+    // when the last scheduled run started and how it ended
+    last_run: Option<String>,
+    last_exit_code: Option<i32>,
 }
 
 impl std::fmt::Display for ProcessStatus {
@@ -58,7 +100,15 @@ impl std::fmt::Display for ProcessStatus {
             format!("{}:{}", self.name, self.seq),
             self.state.to_string(),
             self.description
-        )
+        )?;
+        if let Some(ref text) = self.notify_status {
+            write!(f, " - {text}")?;
+        }
+        match (&self.last_run, self.last_exit_code) {
+            (Some(at), Some(code)) => write!(f, " (last run {at}, exit code {code})"),
+            (Some(at), None) => write!(f, " (last run {at})"),
+            _ => Ok(()),
+        }
     }
 }
 
@@ -69,6 +119,20 @@ impl ProcessStatus {
             seq,
             state,
             description,
+            notify_status: None,
+            last_run: None,
+            last_exit_code: None,
         }
     }
+
+    pub fn with_notify_status(mut self, text: Option<String>) -> Self {
+        self.notify_status = text;
+        self
+    }
+
+    pub fn with_last_run(mut self, at: Option<String>, exit_code: Option<i32>) -> Self {
+        self.last_run = at;
+        self.last_exit_code = exit_code;
+        self
+    }
 }
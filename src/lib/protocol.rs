@@ -0,0 +1,60 @@
+use std::io::{self, BufRead, ErrorKind, Write};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::response::Response;
+
+// bumped on every change tmctl and taskmasterd have to agree on
+pub const PROTOCOL_VERSION: u32 = 2;
+
+// a connection is a sequence of JSON messages, one per line. both sides start with
+// a Hello, then the client sends requests and gets a Reply for each, in order
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Hello {
+    pub version: u32,
+}
+
+impl Default for Hello {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hello {
+    pub fn new() -> Self {
+        Hello {
+            version: PROTOCOL_VERSION,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Reply {
+    // the id of the request answered
+    pub id: u64,
+    pub response: Response,
+}
+
+impl Reply {
+    pub fn new(id: u64, response: Response) -> Self {
+        Reply { id, response }
+    }
+}
+
+pub fn write_message<T: Serialize>(mut writer: impl Write, message: &T) -> io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line)
+}
+
+// None once the other side closed the connection
+pub fn read_message<T: DeserializeOwned>(reader: &mut impl BufRead) -> io::Result<Option<T>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
@@ -1,43 +1,47 @@
 use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use crate::response::Response;
+use crate::response::{Error, Response};
 
-pub type Procedure<A> = Arc<dyn Fn(A) -> Response + Sync + Send>;
+// a method, given the params of a request yet to be deserialized into what it takes
+pub type Procedure = Arc<dyn Fn(Value) -> Result<Response, Error> + Sync + Send>;
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Request {
+    // echoed in the reply, unique within a connection
+    pub id: u64,
     pub method: String,
-    pub args: Vec<String>,
+    // one of lib::params, depending on the method
+    pub params: Value,
 }
 
 impl Request {
-    pub fn from(words: &Vec<&str>) -> Self {
-        let mut args: Vec<String> = Default::default();
-        words.iter().skip(1).for_each(|x| args.push(x.to_string()));
+    pub fn new(id: u64, method: &str, params: Value) -> Self {
         Request {
-            method: words[0].to_owned(),
-            args,
+            id,
+            method: method.to_owned(),
+            params,
         }
     }
 }
 
-pub struct ReqMethod<A> {
-    method: Procedure<A>,
-    args: A,
+pub struct ReqMethod {
+    method: Procedure,
+    params: Value,
 }
 
-impl<A> ReqMethod<A> {
-    pub fn new(procedure: Procedure<A>, args: A) -> Self {
+impl ReqMethod {
+    pub fn new(procedure: Procedure, params: Value) -> Self {
         Self {
             method: procedure,
-            args,
+            params,
         }
     }
 
     pub fn run(self) -> Response {
-        let Self { method, args } = self;
-        method(args)
+        let Self { method, params } = self;
+        method(params).unwrap_or_else(Response::from_err)
     }
 }
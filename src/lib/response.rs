@@ -28,7 +28,7 @@ impl std::fmt::Display for Response {
             Response::Action(ref act) => write!(f, "{act}"),
             Response::Status(ref v) => {
                 for stat in v {
-                    write!(f, "{}\n", stat)?;
+                    writeln!(f, "{}", stat)?;
                 }
                 Ok(())
             }
@@ -41,6 +41,12 @@ pub struct Action {
     pub list: Vec<Result<OutputMessage, Error>>,
 }
 
+impl Default for Action {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Action {
     pub fn new() -> Self {
         Action { list: Vec::new() }
@@ -75,10 +81,10 @@ impl std::fmt::Display for Action {
         for one in self.list.iter() {
             match one {
                 Ok(o) => {
-                    write!(f, "{}\n", o)?;
+                    writeln!(f, "{}", o)?;
                 }
                 Err(e) => {
-                    write!(f, "{}\n", e)?;
+                    writeln!(f, "{}", e)?;
                 }
             }
         }
@@ -96,6 +102,15 @@ pub enum Error {
     ProcessNotRunning(String),
     ProcessAlreadyStarted(String),
     ProcessSpawnError(String),
+    ProcessNoStdin(String),
+    ProcessStdinError(String),
+    ProcessNotReady(String),
+    ProcessNotWatched(String),
+    RestartAborted(String),
+    UnexpectedState(String, String),
+    WaitTimeout(String, String),
+    // the protocol versions of tmctl and taskmasterd, 0 for the one before the handshake
+    ProtocolMismatch(u32, u32),
 }
 
 impl Error {
@@ -131,6 +146,18 @@ impl std::fmt::Display for Error {
             Error::ProcessNotRunning(ref s) => write!(f, "{s}: not running."),
             Error::ProcessAlreadyStarted(ref s) => write!(f, "{s}: already started."),
             Error::ProcessSpawnError(ref s) => write!(f, "{s}: can not spawn process."),
+            Error::ProcessNoStdin(ref s) => write!(f, "{s}: stdin is not a pipe."),
+            Error::ProcessStdinError(ref s) => write!(f, "{s}: can not write to stdin."),
+            Error::ProcessNotReady(ref s) => write!(f, "{s}: did not get ready."),
+            Error::ProcessNotWatched(ref s) => write!(f, "{s}: watches no files."),
+            Error::RestartAborted(ref s) => write!(f, "{s}: failed, rolling restart aborted."),
+            Error::UnexpectedState(ref s, ref state) => write!(f, "{s}: ended up {state}."),
+            Error::WaitTimeout(ref s, ref state) => write!(f, "{s}: still {state}, timed out."),
+            Error::ProtocolMismatch(client, server) => write!(
+                f,
+                "protocol version mismatch: tmctl speaks {client}, taskmasterd speaks {server}. \
+                 upgrade the older one."
+            ),
         }
     }
 }
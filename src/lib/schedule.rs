@@ -0,0 +1,178 @@
+use std::mem::MaybeUninit;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use libc::{localtime_r, time_t, tm};
+
+// a crontab time specification: "minute hour day-of-month month day-of-week"
+// every field takes *, n, a-b and a list of those, each with an optional /step
+#[derive(Debug, PartialEq, Clone)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // cron runs when either day field matches if both are restricted
+    any_day: bool,
+    any_weekday: bool,
+}
+
+#[derive(Debug)]
+pub struct ScheduleError;
+
+// the parts of the local time a schedule looks at
+pub struct LocalTime {
+    pub minute: u32,
+    pub hour: u32,
+    pub day: u32,
+    pub month: u32,
+    // 0 is sunday
+    pub weekday: u32,
+}
+
+impl LocalTime {
+    pub fn at(seconds: i64) -> Self {
+        let mut datetime = unsafe { MaybeUninit::<tm>::zeroed().assume_init() };
+        unsafe { localtime_r(&(seconds as time_t), &mut datetime) };
+        LocalTime {
+            minute: datetime.tm_min as u32,
+            hour: datetime.tm_hour as u32,
+            day: datetime.tm_mday as u32,
+            month: datetime.tm_mon as u32 + 1,
+            weekday: datetime.tm_wday as u32,
+        }
+    }
+}
+
+pub fn epoch_seconds() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+impl Schedule {
+    pub fn matches(&self, t: &LocalTime) -> bool {
+        let day = self.days & (1 << t.day) != 0;
+        let weekday = self.weekdays & (1 << t.weekday) != 0;
+        let day = match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        };
+        self.minutes & (1 << t.minute) != 0
+            && self.hours & (1 << t.hour) != 0
+            && self.months & (1 << t.month) != 0
+            && day
+    }
+
+    // one field as a bitmask of the values it matches
+    fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, ScheduleError> {
+        let mut bits = 0u64;
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, step.parse().map_err(|_| ScheduleError)?),
+                None => (part, 1),
+            };
+            let (first, last) = match range.split_once('-') {
+                _ if range == "*" => (min, max),
+                Some((a, b)) => (
+                    a.parse().map_err(|_| ScheduleError)?,
+                    b.parse().map_err(|_| ScheduleError)?,
+                ),
+                // n/step runs from n to the end of the range
+                None if part.contains('/') => (range.parse().map_err(|_| ScheduleError)?, max),
+                None => {
+                    let n = range.parse().map_err(|_| ScheduleError)?;
+                    (n, n)
+                }
+            };
+            if step == 0 || first < min || last > max || first > last {
+                return Err(ScheduleError);
+            }
+            (first..=last).step_by(step).for_each(|n| bits |= 1 << n);
+        }
+        Ok(bits)
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = ScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(ScheduleError);
+        };
+        let mut weekdays = Schedule::parse_field(weekday, 0, 7)?;
+        // both 0 and 7 are sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Schedule {
+            minutes: Schedule::parse_field(minute, 0, 59)?,
+            hours: Schedule::parse_field(hour, 0, 23)?,
+            days: Schedule::parse_field(day, 1, 31)?,
+            months: Schedule::parse_field(month, 1, 12)?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(minute: u32, hour: u32, day: u32, month: u32, weekday: u32) -> LocalTime {
+        LocalTime {
+            minute,
+            hour,
+            day,
+            month,
+            weekday,
+        }
+    }
+
+    #[test]
+    fn test_every_five_minutes() {
+        let s: Schedule = "*/5 * * * *".parse().unwrap();
+        assert!(s.matches(&at(0, 3, 1, 1, 0)));
+        assert!(s.matches(&at(55, 23, 31, 12, 6)));
+        assert!(!s.matches(&at(7, 3, 1, 1, 0)));
+    }
+
+    #[test]
+    fn test_ranges_and_lists() {
+        let s: Schedule = "30 9-17/2 * 1,6 1-5".parse().unwrap();
+        assert!(s.matches(&at(30, 11, 15, 6, 3)));
+        assert!(!s.matches(&at(30, 10, 15, 6, 3)));
+        assert!(!s.matches(&at(30, 11, 15, 7, 3)));
+        assert!(!s.matches(&at(30, 11, 15, 6, 0)));
+    }
+
+    #[test]
+    fn test_day_or_weekday() {
+        // the 1st of the month or any sunday, 7 being sunday too
+        let s: Schedule = "0 0 1 * 7".parse().unwrap();
+        assert!(s.matches(&at(0, 0, 1, 5, 3)));
+        assert!(s.matches(&at(0, 0, 12, 5, 0)));
+        assert!(!s.matches(&at(0, 0, 12, 5, 3)));
+    }
+
+    #[test]
+    fn test_invalid() {
+        for s in [
+            "* * * *",
+            "60 * * * *",
+            "* * 0 * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+        ] {
+            assert!(s.parse::<Schedule>().is_err(), "{s}");
+        }
+    }
+}
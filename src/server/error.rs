@@ -16,7 +16,8 @@ impl ArgError {
 
 impl Display for ArgError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "usage: {} [conf_file]", self.prog_name)?;
+        writeln!(f, "usage: {} [-n] [conf_file]", self.prog_name)?;
+        writeln!(f, "  -n  run in the foreground (same as nodaemon=true)")?;
         write!(
             f,
             "if conf_file is missing, default ({CONF_FILE}) will be used."
@@ -25,3 +26,24 @@ impl Display for ArgError {
 }
 
 impl Error for ArgError {}
+
+#[derive(Debug)]
+pub struct SocketInUseError {
+    path: String,
+}
+
+impl SocketInUseError {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+        }
+    }
+}
+
+impl Display for SocketInUseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: socket is in use by another server", self.path)
+    }
+}
+
+impl Error for SocketInUseError {}
@@ -0,0 +1,49 @@
+use std::fs::{read_dir, read_to_string};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use lib::logger::LOG;
+
+use nix::errno::Errno;
+use nix::unistd::{getpid, Pid};
+
+// init mode: taskmasterd is (or stands in for) the init process of a container.
+// it reaps every child, not only the ones it spawned
+static INIT_MODE: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() -> Result<(), Errno> {
+    if getpid() != Pid::from_raw(1) {
+        // orphaned grandchildren get reparented to us instead of the real init
+        let ret = unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0) };
+        Errno::result(ret)?;
+        LOG.info("init mode - became a child subreaper");
+    } else {
+        LOG.info("init mode - running as pid 1");
+    }
+    INIT_MODE.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    INIT_MODE.load(Ordering::Relaxed)
+}
+
+// every child of taskmasterd, reparented orphans included
+pub fn children() -> Vec<Pid> {
+    let me = getpid().as_raw();
+    let Ok(entries) = read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|e| e.ok()?.file_name().to_str()?.parse::<i32>().ok())
+        .filter(|pid| parent_of(*pid) == Some(me))
+        .map(Pid::from_raw)
+        .collect()
+}
+
+// /proc/<pid>/stat: "pid (comm) state ppid ...", comm may contain spaces
+fn parent_of(pid: i32) -> Option<i32> {
+    let stat = read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let (_, rest) = stat.rsplit_once(')')?;
+    rest.split_whitespace().nth(1)?.parse().ok()
+}
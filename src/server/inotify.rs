@@ -0,0 +1,100 @@
+use std::ffi::{CString, OsStr, OsString};
+use std::io;
+use std::mem::size_of;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+// a file is watched through its directory, so that replacing it (e.g. a rebuild
+// renaming a new binary over the old one) is seen too
+const FILE_EVENTS: u32 =
+    libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE | libc::IN_DELETE;
+const DIR_EVENTS: u32 = FILE_EVENTS | libc::IN_MOVED_FROM;
+
+// changes to a set of files and directories (not their subdirectories)
+pub struct Inotify {
+    fd: OwnedFd,
+    // watch descriptor, and the file name in its directory if a file is watched
+    watches: Vec<(i32, Option<OsString>)>,
+}
+
+impl Inotify {
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Inotify {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            watches: Vec::new(),
+        })
+    }
+
+    // a file does not have to exist yet, its directory does
+    pub fn add(&mut self, path: &Path) -> io::Result<()> {
+        let watch = if path.is_dir() {
+            (self.add_watch(path, DIR_EVENTS)?, None)
+        } else {
+            let dir = match path.parent() {
+                Some(p) if p != Path::new("") => p.to_owned(),
+                _ => PathBuf::from("."),
+            };
+            let name = path
+                .file_name()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+            (self.add_watch(&dir, FILE_EVENTS)?, Some(name.to_owned()))
+        };
+        self.watches.push(watch);
+        Ok(())
+    }
+
+    fn add_watch(&self, dir: &Path, mask: u32) -> io::Result<i32> {
+        let path = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+        // a directory watched twice keeps its descriptor, the masks are merged
+        let wd = unsafe {
+            libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), mask | libc::IN_MASK_ADD)
+        };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(wd)
+    }
+
+    // whether a watched file or directory changed since the last call
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                )
+            };
+            if n <= 0 {
+                return changed;
+            }
+            let mut offset = 0;
+            while offset + size_of::<libc::inotify_event>() <= n as usize {
+                let event = unsafe {
+                    ptr::read_unaligned(buf[offset..].as_ptr() as *const libc::inotify_event)
+                };
+                let start = offset + size_of::<libc::inotify_event>();
+                offset = start + event.len as usize;
+                if event.mask & libc::IN_IGNORED != 0 {
+                    continue;
+                }
+                let name = buf[start..offset.min(n as usize)]
+                    .split(|b| *b == 0)
+                    .next()
+                    .unwrap_or_default();
+                changed |= self.watches.iter().any(|(wd, file)| {
+                    *wd == event.wd && file.as_ref().is_none_or(|f| f == OsStr::from_bytes(name))
+                });
+            }
+        }
+    }
+}
@@ -1,49 +1,114 @@
 mod control;
 mod error;
+mod init;
+mod inotify;
 mod net;
 mod supervisor;
 
 use error::ArgError;
 use lib::config::Config;
-use lib::daemon::daemonize;
+use lib::daemon::{daemonize, keep_std_logfile, PidFile};
 use lib::logger::LOG;
-use lib::{CONF_FILE, LOG_FILE};
+use lib::CONF_FILE;
+use nix::unistd::{getpid, Pid};
 
 use net::UdsRpcServer;
 use std::env;
+use std::process::{Command, ExitCode};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use supervisor::SupvArg;
+use std::time::Duration;
+use supervisor::State;
 
-fn init() -> Result<Arc<UdsRpcServer<SupvArg>>, Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
+type Daemon = (Arc<UdsRpcServer>, PidFile);
 
-    let conf_file = match args.len() {
-        1 => Ok(CONF_FILE),
-        2 => Ok(args[1].as_str()),
-        _ => Err(Box::new(ArgError::new(&args[0]))),
-    }?;
+struct Args<'a> {
+    conf_file: &'a str,
+    nodaemon: bool,
+    // state file left by the taskmasterd which exec'ed this one (see supervisor::handover)
+    restore: Option<&'a str>,
+}
+
+fn parse_args(args: &[String]) -> Result<Args<'_>, ArgError> {
+    let mut conf_file = None;
+    let mut nodaemon = false;
+    let mut restore = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-n" => nodaemon = true,
+            "--restore" => {
+                restore = Some(iter.next().ok_or_else(|| ArgError::new(&args[0]))?.as_str())
+            }
+            _ if conf_file.is_none() && !arg.starts_with('-') => conf_file = Some(arg.as_str()),
+            _ => return Err(ArgError::new(&args[0])),
+        }
+    }
+    Ok(Args {
+        conf_file: conf_file.unwrap_or(CONF_FILE),
+        nodaemon,
+        restore,
+    })
+}
 
+fn init(args: &Args) -> Result<Daemon, Box<dyn std::error::Error>> {
+    let conf_file = args.conf_file;
+    let conf = Config::from(conf_file)?;
+    let mut pidfile = PidFile::lock(&conf.general.pidfile)?;
+
+    LOG.configure(&conf.general)?;
     control::set_signal_handlers();
-    daemonize(LOG_FILE)?;
+    let init_mode = conf.general.init || getpid() == Pid::from_raw(1);
+    if init_mode {
+        init::enable()?;
+    }
+    // a restored taskmasterd is already detached, and must stay the parent of its processes.
+    // in init mode, the container would end with the process which forked us
+    if !args.nodaemon && !conf.general.nodaemon && !init_mode {
+        match args.restore {
+            None => daemonize(&conf.general.logfile)?,
+            Some(_) => keep_std_logfile(&conf.general.logfile),
+        }
+    }
+    pidfile.write_pid()?;
 
     LOG.info(&format!("read config file from {conf_file}"));
-    let conf = Config::from(conf_file)?;
+    let state = match args.restore {
+        Some(path) => {
+            LOG.info(&format!("restore processes from {path}"));
+            State::load(path)?
+        }
+        None => State::default(),
+    };
     let sock_file = conf.general.sockfile.to_string();
 
     let mut server = UdsRpcServer::new(&sock_file)?;
     LOG.info(&format!("RPC server listen at {}", sock_file));
 
-    supervisor::init(conf_file, conf)?;
+    supervisor::init(conf_file, conf, state)?;
     supervisor::register_rpc(&mut server);
 
-    Ok(Arc::new(server))
+    Ok((Arc::new(server), pidfile))
+}
+
+// the binary at the path we were started from may have been upgraded meanwhile
+fn next_taskmasterd(program: &str, args: &Args) -> Command {
+    let mut command = Command::new(program);
+    command.arg(args.conf_file);
+    if args.nodaemon {
+        command.arg("-n");
+    }
+    command
 }
 
-fn main() {
-    let server = match init() {
+fn main() -> ExitCode {
+    let argv: Vec<String> = env::args().collect();
+    let args = parse_args(&argv).unwrap_or_else(|e| lib::exit_with_log(Box::new(e)));
+
+    let (server, _pidfile) = match init(&args) {
         Err(e) => lib::exit_with_log(e),
-        Ok(server) => server,
+        Ok(daemon) => daemon,
     };
 
     loop {
@@ -59,11 +124,29 @@ fn main() {
             control::UPDATE.store(false, Ordering::Release);
         }
 
+        if control::REOPEN.load(Ordering::Acquire) {
+            LOG.info("reopen signal (USR2) detected.. reopening log files.");
+            supervisor::reopen_logs();
+            control::REOPEN.store(false, Ordering::Release);
+        }
+
+        if control::REEXEC.load(Ordering::Acquire) {
+            LOG.info("re-exec requested.. handing processes over to a new taskmasterd");
+            let e = supervisor::handover(next_taskmasterd(&argv[0], &args));
+            LOG.crit(&format!("re-exec failed - {e}"));
+            control::REEXEC.store(false, Ordering::Release);
+        }
+
         if control::SHUTDOWN.load(Ordering::Relaxed) {
             LOG.info("shutdown signal detected.. cleaning up");
             supervisor::cleanup_processes();
+            if init::enabled() {
+                supervisor::terminate_orphans();
+            }
+            server.wait_clients(Duration::from_secs(1));
 
             break;
         }
     }
+    ExitCode::from(supervisor::exit_code())
 }
@@ -1,33 +1,55 @@
+use crate::error::SocketInUseError;
+
 use lib::logger::LOG;
+use lib::params::Params;
+use lib::protocol::{read_message, write_message, Hello, Reply, PROTOCOL_VERSION};
 use lib::request::{Procedure, ReqMethod, Request};
 use lib::response::{Error as RpcError, Response};
 
-use serde::Deserialize;
-use serde_json;
-
+use serde_json::Value;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fs::remove_file;
 use std::fs::set_permissions;
 use std::fs::Permissions;
+use std::io::{BufRead, BufReader};
 use std::os::unix::fs::PermissionsExt;
 use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+// a session gets the id of its request, to reply to it before it takes the connection over
+pub type Session = Arc<dyn Fn(Value, u64, &UnixStream) -> Result<(), RpcError> + Sync + Send>;
 
-pub struct UdsRpcServer<ARG> {
+pub struct UdsRpcServer {
     listener: UnixListener,
-    methods: HashMap<String, Procedure<ARG>>,
-    validator: Option<fn(&Request) -> Result<ARG, RpcError>>,
+    methods: HashMap<String, Procedure>,
+    sessions: HashMap<String, Session>,
+    // requests being served, an idle connection waits for nothing
+    serving: AtomicUsize,
 }
 
-impl<ARG: 'static + Default> UdsRpcServer<ARG> {
+// the params of a request, as the type its method takes
+fn parse_params<P: Params>(params: Value) -> Result<P, RpcError> {
+    let params: P = serde_json::from_value(params).map_err(|e| {
+        LOG.warn(&format!("invalid params - {e}"));
+        RpcError::invalid_request("argument")
+    })?;
+    params.validate()?;
+    Ok(params)
+}
+
+impl UdsRpcServer {
     pub fn new(path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::remove_stale_socket(path)?;
         let server = UdsRpcServer {
             listener: UnixListener::bind(path)?,
             methods: HashMap::new(),
-            validator: None,
+            sessions: HashMap::new(),
+            serving: AtomicUsize::new(0),
         };
         server.listener.set_nonblocking(true)?;
         set_permissions(path, Permissions::from_mode(0o600))?;
@@ -35,95 +57,182 @@ impl<ARG: 'static + Default> UdsRpcServer<ARG> {
         Ok(server)
     }
 
-    pub fn add_method<F>(&mut self, key: &str, method: F)
+    // a socket file left by a crashed server refuses connections
+    fn remove_stale_socket(path: &str) -> Result<(), Box<dyn Error>> {
+        if !Path::new(path).exists() {
+            return Ok(());
+        }
+        if UnixStream::connect(path).is_ok() {
+            return Err(Box::new(SocketInUseError::new(path)));
+        }
+        LOG.warn(&format!("remove stale socket file - {path}"));
+        remove_file(path)?;
+        Ok(())
+    }
+
+    // a request whose params are not what the method takes is refused before it runs
+    pub fn add_method<P, F>(&mut self, key: &str, method: F)
     where
-        F: (Fn(ARG) -> Response) + 'static + Sync + Send,
+        P: Params,
+        F: (Fn(P) -> Result<Response, RpcError>) + 'static + Sync + Send,
     {
+        let method = move |params| method(parse_params(params)?);
         self.methods.insert(key.to_string(), Arc::new(method));
     }
 
-    pub fn set_validator(&mut self, validator: fn(&Request) -> Result<ARG, RpcError>) {
-        self.validator = Some(validator)
+    // a session takes over the connection until it returns. an error means it did not
+    // start, and is the reply to the request
+    pub fn add_session<P, F>(&mut self, key: &str, session: F)
+    where
+        P: Params,
+        F: Fn(P, u64, &UnixStream) -> Result<(), RpcError> + 'static + Sync + Send,
+    {
+        let session =
+            move |params, id, socket: &UnixStream| session(parse_params(params)?, id, socket);
+        self.sessions.insert(key.to_string(), Arc::new(session));
     }
 
-    fn get_request(&self, socket: &UnixStream) -> Result<ReqMethod<ARG>, RpcError> {
-        let mut deserializer = serde_json::Deserializer::from_reader(socket);
-
-        let req = Request::deserialize(&mut deserializer).map_err(|e| {
-            LOG.warn(&format!("failed to receive request - {e}"));
-            RpcError::service("request not received")
-        })?; // FIXME 타임아웃..?
+    // false if the client speaks another version. a client from before the handshake
+    // sends a request right away, and is answered the way it expects
+    fn handshake(&self, reader: &mut impl BufRead, socket: &UnixStream) -> bool {
+        // such a request has no newline: the first value is parsed as soon as it is complete
+        let first = serde_json::Deserializer::from_reader(&mut *reader)
+            .into_iter::<Value>()
+            .next();
+        let version = match first {
+            None => return false,
+            Some(Ok(value)) => match serde_json::from_value::<Hello>(value) {
+                Ok(hello) => hello.version,
+                Err(_) => 0,
+            },
+            Some(Err(_)) => 0,
+        };
+        if version == 0 {
+            LOG.warn("client without protocol version refused");
+            let e = RpcError::ProtocolMismatch(0, PROTOCOL_VERSION);
+            serde_json::to_writer(socket, &Response::from_err(e)).unwrap_or_default();
+            return false;
+        }
+        // the rest of the hello line
+        reader.read_line(&mut String::new()).unwrap_or_default();
+        write_message(socket, &Hello::new()).unwrap_or_default();
+        if version != PROTOCOL_VERSION {
+            LOG.warn(&format!(
+                "client speaks protocol {version}, expected {PROTOCOL_VERSION} - connection closed"
+            ));
+            return false;
+        }
+        true
+    }
 
-        let method = match self.methods.get(&req.method) {
+    fn get_request(&self, req: Request) -> Result<ReqMethod, RpcError> {
+        match self.methods.get(&req.method) {
             Some(m) => {
                 LOG.info(&format!(
-                    "new request received - method={}, argument={:?}",
-                    &req.method, &req.args
+                    "new request received - method={}, params={}",
+                    &req.method, &req.params
                 ));
-                Ok(m.clone())
+                Ok(ReqMethod::new(m.clone(), req.params))
             }
             None => {
                 LOG.warn(&format!("unknown method found - {}", &req.method));
                 Err(RpcError::invalid_request("method"))
             }
-        }?;
-
-        let args = self.validate_request(&req)?;
-
-        Ok(ReqMethod::new(method, args))
+        }
     }
 
-    fn validate_request(&self, args: &Request) -> Result<ARG, RpcError> {
-        if let Some(v) = self.validator.as_ref() {
-            v(args)
-        } else {
-            Ok(ARG::default())
+    fn run_session(&self, session: &Session, req: Request, socket: &UnixStream) {
+        LOG.info(&format!(
+            "new session requested - method={}, params={}",
+            &req.method, &req.params
+        ));
+        if let Err(e) = session(req.params, req.id, socket) {
+            LOG.warn(&format!("failed to start session - {e}"));
+            write_message(socket, &Reply::new(req.id, Response::from_err(e))).unwrap_or_default();
         }
+        LOG.info(&format!("session closed - method={}", &req.method));
     }
 
+    // requests are answered one after the other until the client leaves,
+    // or a session takes the connection over
     fn handle_client(&self, socket: &UnixStream) {
-        let req = match self.get_request(socket) {
-            Ok(o) => o,
-            Err(e) => {
-                LOG.warn(&format!("failed to handle client - {e}"));
-                serde_json::to_writer(socket, &Response::from_err(e)).unwrap_or_default();
-                socket
-                    .shutdown(std::net::Shutdown::Both)
-                    .unwrap_or_default();
+        let mut reader = BufReader::new(socket);
+        if self.handshake(&mut reader, socket) {
+            self.serve_requests(&mut reader, socket);
+        }
+        socket
+            .shutdown(std::net::Shutdown::Both)
+            .unwrap_or_default();
+    }
+
+    fn serve_requests(&self, reader: &mut impl BufRead, socket: &UnixStream) {
+        loop {
+            let req = match read_message::<Request>(reader) {
+                Ok(Some(req)) => req,
+                Ok(None) => return,
+                Err(e) => {
+                    LOG.warn(&format!("failed to receive request - {e}"));
+                    let e = RpcError::service("request not received");
+                    write_message(socket, &Reply::new(0, Response::from_err(e)))
+                        .unwrap_or_default();
+                    return;
+                }
+            };
+
+            self.serving.fetch_add(1, Ordering::AcqRel);
+            let served = self.serve_request(req, socket);
+            self.serving.fetch_sub(1, Ordering::AcqRel);
+            if !served {
                 return;
             }
-        };
+        }
+    }
 
-        let res = req.run();
-        if let Err(e) = serde_json::to_writer(socket, &res) {
-            LOG.warn(&format!(
-                "fail to resoponse to client - response={}, error={e}",
-                res
-            ));
-            socket
-                .shutdown(std::net::Shutdown::Both)
-                .unwrap_or_default();
+    // false once the connection is not for requests anymore
+    fn serve_request(&self, req: Request, socket: &UnixStream) -> bool {
+        if let Some(session) = self.sessions.get(&req.method) {
+            self.run_session(session, req, socket);
+            return false;
         }
 
-        LOG.info(&format!("request handled - response=\n{}", res));
+        let id = req.id;
+        let res = match self.get_request(req) {
+            Ok(method) => method.run(),
+            Err(e) => {
+                LOG.warn(&format!("failed to handle request - {e}"));
+                Response::from_err(e)
+            }
+        };
+        LOG.info(&format!("request handled - id={id}, response=\n{res}"));
+        if let Err(e) = write_message(socket, &Reply::new(id, res)) {
+            LOG.warn(&format!("fail to resoponse to client - error={e}"));
+            return false;
+        }
+        true
     }
 
     pub fn accept_client(self: &Arc<Self>) {
         if let Ok((socket, ..)) = self.listener.accept() {
             let this = self.clone();
-
-            thread::spawn(move || {
-                this.handle_client(&socket);
-            });
+            thread::spawn(move || this.handle_client(&socket));
         } else {
             thread::sleep(Duration::from_millis(lib::EVENT_LOOP_TIME));
         }
     }
+
+    // give the clients being served (e.g. the one which asked for shutdown) a chance to get
+    // their response before the process exits
+    pub fn wait_clients(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        while self.serving.load(Ordering::Acquire) != 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(lib::EVENT_LOOP_TIME));
+        }
+    }
 }
 
-unsafe impl<A> Send for UdsRpcServer<A> {}
+unsafe impl Send for UdsRpcServer {}
 
-impl<A> Drop for UdsRpcServer<A> {
+impl Drop for UdsRpcServer {
     fn drop(&mut self) {
         let socket_file = self.listener.local_addr().unwrap();
         let socket_file = socket_file.as_pathname().unwrap();
@@ -1,41 +1,66 @@
 mod process;
+mod state;
 
 use std::collections::HashMap;
 use std::error::Error;
+use std::fs::remove_file;
+use std::io::{ErrorKind, Read, Write};
 use std::mem::MaybeUninit;
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::Command;
+use std::ptr::{addr_of, addr_of_mut};
 use std::sync::atomic::Ordering;
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
 use std::vec::Vec;
 
 use lib::config::{Config, ProgramConfig};
+use lib::daemon;
 use lib::logger::LOG;
+use lib::params::{
+    ControlParams, FgParams, RestartParams, SendStdinParams, SignalParams, Targets, WaitParams,
+    WatchParams,
+};
 use lib::process_id::ProcessId;
-use lib::process_status::ProcessStatus;
-use lib::request::Request;
+use lib::process_status::{ProcessState, ProcessStatus};
+use lib::protocol::{write_message, Reply};
 use lib::response::{
     Action, Error as RpcError, OutputMessage as RpcOutput, Response as RpcResponse,
 };
 
+use nix::sys::signal::{self, Signal};
+
+use crate::inotify::Inotify;
 use crate::net::UdsRpcServer;
 
 use super::control;
 use process::*;
+pub use state::State;
+use state::{Intent, Intents};
 
-pub type SupvArg = Vec<ProcessId>;
+const ORPHAN_STOPWAITSECS: u64 = 2;
+// how long start, stop and restart --wait for the processes by default
+const WAIT_TIMEOUT_SECS: u64 = 30;
+// editors write a file in several steps, update once it settled
+const AUTORELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
 
 static mut SUPERVISOR: MaybeUninit<Mutex<Supervisor>> = MaybeUninit::uninit();
 
-pub fn init(conf_file: &str, conf: Config) -> Result<(), Box<dyn Error>> {
-    let supervisor = Supervisor::new(conf_file, conf)?;
+pub fn init(conf_file: &str, conf: Config, state: State) -> Result<(), Box<dyn Error>> {
+    let supervisor = Supervisor::new(conf_file, conf, state)?;
 
-    unsafe { SUPERVISOR.write(Mutex::new(supervisor)) };
+    unsafe { (*addr_of_mut!(SUPERVISOR)).write(Mutex::new(supervisor)) };
 
     Ok(())
 }
 
 fn supervisor<'a>() -> MutexGuard<'a, Supervisor> {
     unsafe {
-        SUPERVISOR
+        (*addr_of!(SUPERVISOR))
             .assume_init_ref()
             .lock()
             .unwrap_or_else(|e| e.into_inner())
@@ -47,23 +72,192 @@ pub fn supervise() -> Result<(), Box<dyn Error>> {
 }
 
 pub fn update() {
-    supervisor().update(Vec::new());
+    supervisor().update();
+}
+
+pub fn reopen_logs() {
+    supervisor().reopenlogs();
 }
 
+// replace taskmasterd with `next`, which adopts every process through the state file.
+// returns only if exec failed, then this taskmasterd keeps supervising
+pub fn handover(mut next: Command) -> Box<dyn Error> {
+    // hold the lock until exec so that no request changes the process table
+    let mut supervisor = supervisor();
+    let path = format!("{}.state", supervisor.config.general.pidfile);
+    let state = supervisor.snapshot();
+
+    if let Err(e) = state.save(&path) {
+        return Box::new(e);
+    }
+    if let Err(e) = state.set_inheritable(true) {
+        remove_file(&path).unwrap_or_default();
+        return Box::new(e);
+    }
+
+    LOG.info(&format!(
+        "handing {} processes over - state={path}",
+        state.processes.len()
+    ));
+    let e = next.arg("--restore").arg(&path).exec();
+
+    state.set_inheritable(false).unwrap_or_default();
+    remove_file(&path).unwrap_or_default();
+    Box::new(e)
+}
+
+// a process which had to be killed makes taskmasterd exit with 1
 pub fn cleanup_processes() {
-    supervisor().cleanup_processes()
+    if !stop_processes() {
+        let mut supervisor = supervisor();
+        if supervisor.exit_code == 0 {
+            supervisor.exit_code = 1;
+        }
+    }
+}
+
+// one process at a time, the last started first. each one is waited for until it is gone,
+// which its stopwaitsecs ends with SIGKILL, as long as shutdown_timeout allows.
+// the lock is released between polls, status requests are answered meanwhile.
+// false if some processes outlived shutdown_timeout and were killed
+fn stop_processes() -> bool {
+    let (order, deadline) = {
+        let supervisor = supervisor();
+        (supervisor.stop_order(), supervisor.shutdown_deadline())
+    };
+    for id in order.iter() {
+        if Instant::now() >= deadline {
+            break;
+        }
+        let _ = supervisor().remove_process(id);
+        wait_stopped(id, deadline);
+    }
+    let mut supervisor = supervisor();
+    // the ones not reached before shutdown_timeout are signalled only to be killed
+    for id in order.iter() {
+        let _ = supervisor.remove_process(id);
+    }
+    supervisor.kill_trashes()
+}
+
+fn wait_stopped(id: &ProcessId, deadline: Instant) {
+    while Instant::now() < deadline {
+        let mut supervisor = supervisor();
+        supervisor.garbage_collect();
+        if !supervisor.trashes.iter().any(|p| p.get_id() == *id) {
+            return;
+        }
+        drop(supervisor);
+        thread::sleep(Duration::from_millis(lib::EVENT_LOOP_TIME));
+    }
+}
+
+// init mode: what is left after the programs stopped are orphans they left behind
+pub fn terminate_orphans() {
+    let orphans = super::init::children();
+    if orphans.is_empty() {
+        return;
+    }
+    LOG.info(&format!("send SIGTERM to {} orphans", orphans.len()));
+    orphans
+        .iter()
+        .for_each(|pid| signal::kill(*pid, Signal::SIGTERM).unwrap_or_default());
+
+    let deadline = Instant::now() + Duration::from_secs(ORPHAN_STOPWAITSECS);
+    while Instant::now() < deadline {
+        process::reap();
+        if super::init::children().is_empty() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(lib::EVENT_LOOP_TIME));
+    }
+    super::init::children()
+        .iter()
+        .for_each(|pid| signal::kill(*pid, Signal::SIGKILL).unwrap_or_default());
+    process::reap();
 }
 
-pub fn register_rpc<'a>(server: &'a mut UdsRpcServer<SupvArg>) {
-    let status = |args| supervisor().status(args);
-    let start = |args| supervisor().start(args);
-    let stop = |args| supervisor().stop(args);
-    let shutdown = |args| supervisor().shutdown(args);
-    let reload = |args| supervisor().reload(args);
-    let update = |args| supervisor().update(args);
-    let restart = |args| supervisor().restart(args);
+// 0 when every process stopped by itself, the exit code of a failed critical process otherwise
+pub fn exit_code() -> u8 {
+    supervisor().exit_code
+}
+
+pub fn register_rpc(server: &mut UdsRpcServer) {
+    let status = |p: Targets| {
+        let supervisor = supervisor();
+        let ids = match p.names.is_empty() {
+            true => supervisor.convert_to_process_ids(&[String::from("all")])?,
+            false => supervisor.convert_to_process_ids(&p.names)?,
+        };
+        Ok(supervisor.status(ids))
+    };
+    let start = |p: ControlParams| {
+        let ids = supervisor().convert_to_process_ids(&p.names)?;
+        let res = supervisor().start(&ids);
+        let res = wait_ready(&ids, res);
+        Ok(wait_states(
+            &ids,
+            wait_timeout(p.wait, p.timeout),
+            res,
+            false,
+        ))
+    };
+    let stop = |p: ControlParams| {
+        let ids = supervisor().convert_to_process_ids(&p.names)?;
+        let res = supervisor().stop(&ids);
+        Ok(wait_states(
+            &ids,
+            wait_timeout(p.wait, p.timeout),
+            res,
+            true,
+        ))
+    };
+    let shutdown = |_: ()| {
+        LOG.info("handle request - shutdown");
+        cleanup_processes();
+        Ok(supervisor().shutdown())
+    };
+    let reload = |_: ()| {
+        LOG.info("handle request - reload");
+        stop_processes();
+        Ok(supervisor().reload())
+    };
+    let update = |_: ()| Ok(supervisor().update());
+    let restart = |p: RestartParams| {
+        let mut ids = supervisor().convert_to_process_ids(&p.names)?;
+        if p.rolling {
+            // batches go in order of instance
+            ids.sort_by(|a, b| (&a.name, a.seq).cmp(&(&b.name, b.seq)));
+            let batch = p.batch.unwrap_or(1) as usize;
+            return Ok(rolling_restart(&ids, batch, p.wait_running));
+        }
+        let res = supervisor().restart(&ids);
+        let res = wait_ready(&ids, res);
+        Ok(wait_states(
+            &ids,
+            wait_timeout(p.wait, p.timeout),
+            res,
+            false,
+        ))
+    };
+    let sendstdin = |p: SendStdinParams| {
+        let mut supervisor = supervisor();
+        let ids = supervisor.convert_to_process_ids(&[p.name])?;
+        Ok(supervisor.sendstdin(&ids, &p.text))
+    };
+    let signal = |p: SignalParams| {
+        let mut supervisor = supervisor();
+        let ids = supervisor.convert_to_process_ids(&p.names)?;
+        Ok(supervisor.signal(&ids, &p.signal))
+    };
+    let reopenlogs = |_: ()| Ok(supervisor().reopenlogs());
+    let reexec = |_: ()| Ok(supervisor().reexec());
+    let watch = |p: WatchParams| {
+        let mut supervisor = supervisor();
+        let ids = supervisor.convert_to_process_ids(&p.names)?;
+        Ok(supervisor.watch(&ids, p.paused))
+    };
 
-    server.set_validator(|req| supervisor().validate(req));
     server.add_method("status", status);
     server.add_method("start", start);
     server.add_method("stop", stop);
@@ -71,6 +265,196 @@ pub fn register_rpc<'a>(server: &'a mut UdsRpcServer<SupvArg>) {
     server.add_method("reload", reload);
     server.add_method("update", update);
     server.add_method("restart", restart);
+    server.add_method("sendstdin", sendstdin);
+    server.add_method("signal", signal);
+    server.add_method("reopenlogs", reopenlogs);
+    server.add_method("reexec", reexec);
+    server.add_method("watch", watch);
+    server.add_method("wait", wait);
+    server.add_session("fg", fg);
+}
+
+// start and restart answer once the processes with a readiness check got ready
+// or gave up. the supervisor is not held meanwhile, the event loop drives them
+fn wait_ready(ids: &[ProcessId], res: RpcResponse) -> RpcResponse {
+    while supervisor().is_getting_ready(ids) {
+        thread::sleep(Duration::from_millis(lib::EVENT_LOOP_TIME));
+    }
+    let RpcResponse::Action(act) = res else {
+        return res;
+    };
+
+    // the results of starting `ids` come last, restart puts its stops first
+    let supervisor = supervisor();
+    let skip = act.list.len().saturating_sub(ids.len());
+    let list = act
+        .list
+        .into_iter()
+        .enumerate()
+        .map(|(i, r)| match (r, i.checked_sub(skip)) {
+            (Ok(out), Some(n)) => supervisor.check_ready(&ids[n]).map(|_| out),
+            (r, _) => r,
+        })
+        .collect();
+    RpcResponse::Action(list)
+}
+
+// how long start, stop or restart waits for the processes, None without --wait
+fn wait_timeout(wait: bool, timeout: Option<u64>) -> Option<Duration> {
+    wait.then(|| Duration::from_secs(timeout.unwrap_or(WAIT_TIMEOUT_SECS)))
+}
+
+// --wait: answer with the state every process ended up in, once none of them is
+// on its way anymore or the timeout expired. the results of `ids` come last
+fn wait_states(
+    ids: &[ProcessId],
+    timeout: Option<Duration>,
+    res: RpcResponse,
+    stopping: bool,
+) -> RpcResponse {
+    let Some(timeout) = timeout else {
+        return res;
+    };
+    let RpcResponse::Action(act) = res else {
+        return res;
+    };
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline && supervisor().is_on_the_way(ids, stopping) {
+        thread::sleep(Duration::from_millis(lib::EVENT_LOOP_TIME));
+    }
+
+    // restart puts its stops first, only their errors are kept
+    let supervisor = supervisor();
+    let skip = act.list.len().saturating_sub(ids.len());
+    let list = act
+        .list
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, r)| match (r, i.checked_sub(skip)) {
+            (Ok(_), Some(n)) => Some(supervisor.settled_state(&ids[n], stopping)),
+            (Ok(_), None) => None,
+            (r, _) => Some(r),
+        })
+        .collect();
+    RpcResponse::Action(list)
+}
+
+// Wait(state, Vec<name>) -> ()
+// answers once every process is in `state`, or with the state of each one when the
+// timeout expired. follows the transitions supervise passes on, without holding the supervisor
+fn wait(p: WaitParams) -> Result<RpcResponse, RpcError> {
+    let wanted = p.state;
+    let ids = supervisor().convert_to_process_ids(&p.names)?;
+    LOG.info(&format!("handle request - wait {}, names={ids:?}", wanted));
+    let timeout = p.timeout.unwrap_or(WAIT_TIMEOUT_SECS);
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+
+    let (mut states, transitions) = supervisor().subscribe(&ids);
+    while states.values().any(|s| *s != wanted) {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match transitions.recv_timeout(left) {
+            Ok((id, state)) => {
+                if let Some(s) = states.get_mut(&id) {
+                    *s = state;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+
+    let act = ids
+        .iter()
+        .map(|id| match states.get(id) {
+            Some(s) if *s == wanted => Ok(RpcOutput::new(&id.to_string(), &s.to_string())),
+            Some(s) => Err(RpcError::WaitTimeout(id.to_string(), s.to_string())),
+            None => Err(RpcError::ProcessNotFound(id.to_string())),
+        })
+        .collect::<Action>();
+    Ok(RpcResponse::Action(act))
+}
+
+// restart --rolling: a batch of processes at a time, the next one once the previous
+// batch is up again, so that the others keep serving. a batch going Fatal aborts it
+fn rolling_restart(targets: &[ProcessId], batch: usize, wait_running: bool) -> RpcResponse {
+    LOG.info(&format!(
+        "handle request - rolling restart, batch={batch}, names={targets:?}"
+    ));
+
+    let mut act = Action::new();
+    for ids in targets.chunks(batch) {
+        let res = supervisor().restart(ids);
+        let res = wait_ready(ids, res);
+        let RpcResponse::Action(res) = res else {
+            return res;
+        };
+        act = act + res;
+
+        let settled = loop {
+            if let Some(settled) = supervisor().batch_settled(ids, wait_running) {
+                break settled;
+            }
+            thread::sleep(Duration::from_millis(lib::EVENT_LOOP_TIME));
+        };
+        if let Err(e) = settled {
+            LOG.warn(&format!("rolling restart aborted - {e}"));
+            act.add_element(Err(e));
+            break;
+        }
+    }
+    RpcResponse::Action(act)
+}
+
+// fg <name:seq>: stream the output of a process to the client and
+// forward every line the client sends to its stdin until the client leaves
+fn fg(p: FgParams, request_id: u64, socket: &UnixStream) -> Result<(), RpcError> {
+    let ids = supervisor().convert_to_process_ids(&[p.name])?;
+    let [id] = ids.as_slice() else {
+        return Err(RpcError::invalid_request("argument"));
+    };
+    let output = supervisor().attach(id)?;
+    let res = RpcResponse::from_output(RpcOutput::new(&id.to_string(), "attached"));
+    if write_message(socket, &Reply::new(request_id, res)).is_ok() {
+        forward(id, output, socket);
+    }
+    Ok(())
+}
+
+fn forward(id: &ProcessId, output: Receiver<Vec<u8>>, socket: &UnixStream) {
+    let timeout = Duration::from_millis(lib::EVENT_LOOP_TIME);
+    if let Err(e) = socket.set_read_timeout(Some(timeout)) {
+        LOG.warn(&format!("failed to set read timeout on session - {e}"));
+        return;
+    }
+
+    let mut stream = socket;
+    let mut input = Vec::new();
+    let mut buf = [0u8; 1024];
+    loop {
+        while let Ok(data) = output.try_recv() {
+            if stream.write_all(&data).is_err() {
+                return;
+            }
+        }
+
+        match stream.read(&mut buf) {
+            Ok(0) => return,
+            Ok(n) => input.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {}
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(_) => return,
+        }
+
+        while let Some(pos) = input.iter().position(|&c| c == b'\n') {
+            let line: Vec<u8> = input.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line[..pos]).to_string();
+            let res = supervisor().send_stdin(id, &line);
+            if let Err(e) = res {
+                if stream.write_all(format!("{e}\n").as_bytes()).is_err() {
+                    return;
+                }
+            }
+        }
+    }
 }
 
 pub struct Supervisor {
@@ -78,52 +462,122 @@ pub struct Supervisor {
     config: Config,
     processes: HashMap<ProcessId, Process>,
     trashes: Vec<Process>,
+    orphans: Vec<Child>,
+    intents: Intents,
+    attached: HashMap<ProcessId, Vec<Sender<Vec<u8>>>>,
+    // wait requests, told about every state a process goes to
+    subscribers: Vec<Sender<(ProcessId, ProcessState)>>,
+    exit_code: u8,
+    // autoreload: the config file, and when it last changed
+    config_watch: Option<Inotify>,
+    config_changed_at: Option<Instant>,
 }
 
 impl Supervisor {
-    fn new(file_path: &str, config: Config) -> Result<Self, Box<dyn std::error::Error>> {
+    fn new(
+        file_path: &str,
+        config: Config,
+        mut state: State,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
         let mut sp = Supervisor {
             file_path: file_path.to_owned(),
             config: Default::default(),
             processes: HashMap::new(),
             trashes: Vec::new(),
+            orphans: Vec::new(),
+            intents: Intents::load(&config.general.statedir),
+            attached: HashMap::new(),
+            subscribers: Vec::new(),
+            exit_code: 0,
+            config_watch: None,
+            config_changed_at: None,
         };
 
         for (_, v) in config.programs.iter() {
             for seq in 0..v.numprocs {
-                sp.add_process(v, seq)?;
+                let adopted = state
+                    .processes
+                    .iter()
+                    .position(|s| s.name == v.name && s.seq == seq)
+                    .map(|i| state.processes.swap_remove(i));
+
+                match adopted {
+                    Some(snapshot) => sp.adopt_process(v, snapshot)?,
+                    None => sp.add_process(v, seq)?,
+                }
             }
         }
+        sp.abandon(state);
         sp.config = config;
+        sp.watch_config();
         Ok(sp)
     }
 
-    fn validate(&self, req: &Request) -> Result<SupvArg, RpcError> {
-        if req.method == "status" && req.args.is_empty() {
-            self.convert_to_process_ids(&vec![String::from("all")])
-        } else {
-            self.convert_to_process_ids(&req.args)
+    // processes of the previous taskmasterd which are not configured anymore
+    fn abandon(&mut self, state: State) {
+        for snapshot in state.processes.into_iter() {
+            let Some(pid) = snapshot.pid else { continue };
+            LOG.warn(&format!(
+                "[{}:{}] is not configured anymore, send SIGTERM - pid={pid}",
+                snapshot.name, snapshot.seq
+            ));
+            let child = Child::new(pid, None);
+            signal::kill(child.pid(), Signal::SIGTERM).unwrap_or_default();
+            self.orphans.push(child);
         }
     }
 
     fn supervise(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        for (_, process) in self.processes.iter_mut() {
+        if super::init::enabled() {
+            process::reap();
+        }
+        self.autoreload();
+        self.start_deferred();
+        for (id, process) in self.processes.iter_mut() {
             process.run()?;
+
+            if process.is_critical_failure() && !control::SHUTDOWN.load(Ordering::Relaxed) {
+                LOG.crit(&format!(
+                    "critical process [{id}] is {}, shutting down",
+                    process.state()
+                ));
+                self.exit_code = process.exit_code();
+                control::SHUTDOWN.store(true, Ordering::Relaxed);
+            }
+
+            for state in process.take_transitions() {
+                self.subscribers
+                    .retain(|tx| tx.send((id.clone(), state)).is_ok());
+            }
+
+            let output = process.take_output();
+            if let Some(listeners) = self.attached.get_mut(id) {
+                if !output.is_empty() {
+                    listeners.retain(|tx| tx.send(output.clone()).is_ok());
+                }
+            }
         }
+        self.attached.retain(|_, listeners| !listeners.is_empty());
         self.garbage_collect();
         Ok(())
     }
 
     fn garbage_collect(&mut self) {
-        self.trashes
-            .iter_mut()
-            .for_each(|p| p.run().unwrap_or_default());
+        // what a replaced process goes through does not concern wait
+        self.trashes.iter_mut().for_each(|p| {
+            p.run().unwrap_or_default();
+            p.take_output();
+            p.take_transitions();
+        });
         self.trashes.retain(|p| !p.is_stopped());
+        self.orphans
+            .retain_mut(|c| matches!(c.try_wait(), Ok(None)));
     }
 
-    fn start(&mut self, inputs: SupvArg) -> RpcResponse {
+    fn start(&mut self, inputs: &[ProcessId]) -> RpcResponse {
         LOG.info(&format!("handle request - start, names={:?}", inputs));
 
+        self.record_intent(inputs, Intent::Started);
         let act = inputs
             .iter()
             .map(|id| self.try_process_operation(id, Process::start))
@@ -131,9 +585,10 @@ impl Supervisor {
         RpcResponse::Action(act)
     }
 
-    fn stop(&mut self, inputs: SupvArg) -> RpcResponse {
+    fn stop(&mut self, inputs: &[ProcessId]) -> RpcResponse {
         LOG.info(&format!("handle request - stop, names={:?}", inputs));
 
+        self.record_intent(inputs, Intent::Stopped);
         let act = inputs
             .iter()
             .map(|id| self.try_process_operation(id, Process::stop))
@@ -141,9 +596,10 @@ impl Supervisor {
         RpcResponse::Action(act)
     }
 
-    fn restart(&mut self, inputs: SupvArg) -> RpcResponse {
+    fn restart(&mut self, inputs: &[ProcessId]) -> RpcResponse {
         LOG.info(&format!("handle request - stop, names={:?}", inputs));
 
+        self.record_intent(inputs, Intent::Started);
         let act = inputs
             .iter()
             .map(|process_id| {
@@ -175,43 +631,236 @@ impl Supervisor {
         RpcResponse::Action(act + act2)
     }
 
-    // Reload() -> ()
-    fn reload(&mut self, _: SupvArg) -> RpcResponse {
-        LOG.info("handle request - reload");
-        self.cleanup_processes();
+    fn sendstdin(&mut self, ids: &[ProcessId], text: &str) -> RpcResponse {
+        LOG.info(&format!("handle request - sendstdin, names={:?}", ids));
+
+        let act = ids
+            .iter()
+            .map(|id| self.send_stdin(id, text))
+            .collect::<Action>();
+        RpcResponse::Action(act)
+    }
+
+    fn signal(&mut self, ids: &[ProcessId], signal: &str) -> RpcResponse {
+        LOG.info(&format!(
+            "handle request - signal {signal}, names={:?}",
+            ids
+        ));
+
+        let signal = match ProgramConfig::parse_signal("signal", signal) {
+            Ok(o) => o,
+            Err(_) => return RpcResponse::from_err(RpcError::invalid_request("signal")),
+        };
+        let act = ids
+            .iter()
+            .map(|id| match self.processes.get_mut(id) {
+                Some(proc) => proc.signal(signal),
+                None => Err(RpcError::ProcessNotFound(id.to_string())),
+            })
+            .collect::<Action>();
+        RpcResponse::Action(act)
+    }
+
+    // Watch(pause|resume, Vec<name>) -> ()
+    fn watch(&mut self, ids: &[ProcessId], paused: bool) -> RpcResponse {
+        LOG.info(&format!(
+            "handle request - watch {}, names={:?}",
+            if paused { "pause" } else { "resume" },
+            ids
+        ));
 
+        let act = ids
+            .iter()
+            .map(|id| match self.processes.get_mut(id) {
+                Some(proc) => proc.set_watch(paused),
+                None => Err(RpcError::ProcessNotFound(id.to_string())),
+            })
+            .collect::<Action>();
+        RpcResponse::Action(act)
+    }
+
+    // the current states of `ids`, and the states they go to from now on
+    fn subscribe(
+        &mut self,
+        ids: &[ProcessId],
+    ) -> (
+        HashMap<ProcessId, ProcessState>,
+        Receiver<(ProcessId, ProcessState)>,
+    ) {
+        let states = ids
+            .iter()
+            .filter_map(|id| Some((id.clone(), self.processes.get(id)?.state())))
+            .collect();
+        let (tx, rx) = channel();
+        self.subscribers.push(tx);
+        (states, rx)
+    }
+
+    fn attach(&mut self, id: &ProcessId) -> Result<Receiver<Vec<u8>>, RpcError> {
+        LOG.info(&format!("handle request - fg, name={id}"));
+
+        match self.processes.get(id) {
+            Some(proc) if proc.is_alive() => {}
+            Some(_) => return Err(RpcError::ProcessNotRunning(id.to_string())),
+            None => return Err(RpcError::ProcessNotFound(id.to_string())),
+        }
+        let (tx, rx) = channel();
+        self.attached.entry(id.clone()).or_default().push(tx);
+        Ok(rx)
+    }
+
+    fn send_stdin(&mut self, id: &ProcessId, text: &str) -> Result<RpcOutput, RpcError> {
+        match self.processes.get_mut(id) {
+            Some(proc) => proc.send_stdin(text),
+            None => Err(RpcError::ProcessNotFound(id.to_string())),
+        }
+    }
+
+    // Reload() -> ()
+    // every process was stopped beforehand (see stop_processes)
+    fn reload(&mut self) -> RpcResponse {
         let turn_on = self.config.process_list();
         for process_id in turn_on {
-            self.revive_process(&process_id).unwrap_or_default();
+            // an update may have run while the lock was released
+            if !self.processes.contains_key(&process_id) {
+                self.revive_process(&process_id).unwrap_or_default();
+            }
         }
         RpcResponse::from_output(RpcOutput::new("taskmasterd", "reload"))
     }
 
     //     Shutdown() -> ()
-    fn shutdown(&mut self, _: SupvArg) -> RpcResponse {
-        LOG.info("handle request - shutdown");
+    // answers once every process has stopped (see cleanup_processes), the main loop
+    // then only has to exit
+    fn shutdown(&mut self) -> RpcResponse {
         control::SHUTDOWN.store(true, Ordering::Relaxed);
         RpcResponse::from_output(RpcOutput::new("taskmasterd", "shutdown"))
     }
 
+    // ReopenLogs() -> ()
+    // processes keep running, only the files they write to are reopened
+    fn reopenlogs(&mut self) -> RpcResponse {
+        LOG.info("handle request - reopenlogs");
+        if let Err(e) = LOG.reopen() {
+            LOG.warn(&format!("failed to reopen log - {e}"));
+        }
+        if let Err(e) = daemon::reopen_std_logfile() {
+            LOG.warn(&format!("failed to reopen stdout and stderr - {e}"));
+        }
+        for process in self.processes.values_mut().chain(self.trashes.iter_mut()) {
+            if let Err(e) = process.reopen_logs() {
+                LOG.warn(&format!(
+                    "failed to reopen logs of [{}] - {e}",
+                    process.get_id()
+                ));
+            }
+        }
+        RpcResponse::from_output(RpcOutput::new("taskmasterd", "logs reopened"))
+    }
+
+    // Reexec() -> ()
+    fn reexec(&mut self) -> RpcResponse {
+        LOG.info("handle request - reexec");
+        control::REEXEC.store(true, Ordering::Relaxed);
+        RpcResponse::from_output(RpcOutput::new("taskmasterd", "reexec"))
+    }
+
+    // every process but the stopping ones (which are waited for) goes to the next taskmasterd
+    fn snapshot(&mut self) -> State {
+        self.wait_trashes(self.shutdown_deadline());
+        State {
+            processes: self.processes.values().map(|p| p.snapshot()).collect(),
+        }
+    }
+
+    fn record_intent(&mut self, ids: &[ProcessId], intent: Intent) {
+        let known: Vec<ProcessId> = ids
+            .iter()
+            .filter(|id| self.processes.contains_key(id))
+            .cloned()
+            .collect();
+        self.intents.set(&known, intent);
+    }
+
+    // a process the operator started or stopped by hand stays that way
+    fn should_start(&self, conf: &ProgramConfig, seq: u32) -> bool {
+        match self.intents.get(&ProcessId::new(conf.name.to_owned(), seq)) {
+            Some(Intent::Started) => true,
+            Some(Intent::Stopped) => false,
+            None => conf.autostart,
+        }
+    }
+
     fn remove_process(&mut self, process_id: &ProcessId) -> Result<(), RpcError> {
         if let Some(mut proc) = self.processes.remove(process_id) {
-            proc.stop()?;
-            self.trashes.push(proc);
+            let ret = proc.stop();
+            // even if the stop signal could not be sent, it is killed at the end of a shutdown
+            if proc.is_alive() {
+                self.trashes.push(proc);
+            }
+            ret?;
+        }
+        Ok(())
+    }
+
+    // with depends_on, autostart waits in supervise until the dependencies are up
+    fn autostart(process: &mut Process, conf: &ProgramConfig) -> Result<(), RpcError> {
+        if conf.depends_on.is_empty() {
+            process.start()?;
+        } else {
+            process.defer_start(&conf.depends_on);
         }
         Ok(())
     }
 
+    fn start_deferred(&mut self) {
+        let ids: Vec<ProcessId> = self
+            .processes
+            .iter()
+            .filter(|(id, p)| p.is_deferred() && self.dependencies_up(&id.name))
+            .map(|(id, _)| id.to_owned())
+            .collect();
+        for id in ids {
+            LOG.info(&format!("[{id}] dependencies are up"));
+            if let Err(e) = self.processes.get_mut(&id).unwrap().start() {
+                LOG.warn(&format!("[{id}] failed to start - {e}"));
+            }
+        }
+    }
+
+    fn dependencies_up(&self, name: &str) -> bool {
+        let Some(conf) = self.config.programs.get(name) else {
+            return false;
+        };
+        conf.depends_on.iter().all(|dep| {
+            self.processes
+                .iter()
+                .filter(|(id, _)| id.name == *dep)
+                .all(|(_, p)| p.is_up())
+        })
+    }
+
     fn add_process(&mut self, conf: &ProgramConfig, seq: u32) -> Result<(), RpcError> {
         let mut process = Process::new(conf, seq)?;
-        if conf.autostart {
-            process.start()?;
+        if self.should_start(conf, seq) {
+            Supervisor::autostart(&mut process, conf)?;
         }
         self.processes.insert(process.get_id(), process);
 
         Ok(())
     }
 
+    fn adopt_process(
+        &mut self,
+        conf: &ProgramConfig,
+        snapshot: state::ProcessSnapshot,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut process = Process::new(conf, snapshot.seq)?;
+        process.restore(snapshot)?;
+        self.processes.insert(process.get_id(), process);
+        Ok(())
+    }
+
     fn revive_process(&mut self, process_id: &ProcessId) -> Result<(), RpcError> {
         let conf = self
             .config
@@ -220,8 +869,8 @@ impl Supervisor {
             .ok_or_else(|| RpcError::ProcessNotFound(process_id.to_string()))?;
 
         let mut process = Process::new(conf, process_id.seq)?;
-        if conf.autostart {
-            process.start()?;
+        if self.should_start(conf, process_id.seq) {
+            Supervisor::autostart(&mut process, conf)?;
         }
         self.processes.insert(process.get_id(), process);
         Ok(())
@@ -255,25 +904,78 @@ impl Supervisor {
         }
     }
 
-    fn update(&mut self, _: SupvArg) -> RpcResponse {
+    fn update(&mut self) -> RpcResponse {
         LOG.info("handle request - update");
         let next_conf = match Config::from(&self.file_path) {
             Ok(o) => o,
-            Err(e) => return RpcResponse::from_err(RpcError::file_format(e.to_string().as_str())),
+            Err(e) => {
+                LOG.warn(&format!("configuration not updated - {e}"));
+                return RpcResponse::from_err(RpcError::file_format(e.to_string().as_str()));
+            }
         };
 
+        if let Err(e) = LOG.configure(&next_conf.general) {
+            LOG.warn(&format!("failed to apply log configuration - {e}"));
+        }
+        self.intents.set_statedir(&next_conf.general.statedir);
+        self.intents.retain(&next_conf.process_list());
         self.affect(&next_conf);
         self.config = next_conf;
+        self.watch_config();
         RpcResponse::from_output(RpcOutput::new("configuration", "updated"))
     }
 
-    fn convert_to_process_ids(&self, names: &Vec<String>) -> Result<Vec<ProcessId>, RpcError> {
+    fn watch_config(&mut self) {
+        self.config_watch = None;
+        self.config_changed_at = None;
+        if !self.config.general.autoreload {
+            return;
+        }
+        // the files included as of this update, and the dirs where new ones would match
+        let watch = Inotify::new().and_then(|mut watch| {
+            watch.add(Path::new(&self.file_path))?;
+            for path in self.config.includes.iter() {
+                watch.add(path)?;
+            }
+            Ok(watch)
+        });
+        match watch {
+            Ok(watch) => self.config_watch = Some(watch),
+            Err(e) => LOG.warn(&format!("failed to watch {} - {e}", self.file_path)),
+        }
+    }
+
+    // an invalid config is rejected by update, the processes keep running as they are
+    fn autoreload(&mut self) {
+        let Some(watch) = self.config_watch.as_ref() else {
+            return;
+        };
+        if watch.changed() {
+            self.config_changed_at = Some(Instant::now());
+        }
+        match self.config_changed_at {
+            Some(at) if at.elapsed() >= AUTORELOAD_DEBOUNCE => {}
+            _ => return,
+        }
+        self.config_changed_at = None;
+        LOG.info(&format!("{} changed, updating", self.file_path));
+        self.update();
+    }
+
+    fn convert_to_process_ids(&self, names: &[String]) -> Result<Vec<ProcessId>, RpcError> {
         if names.contains(&String::from("all")) {
-            Ok(Vec::from_iter(self.config.process_list().into_iter()))
+            Ok(Vec::from_iter(self.config.process_list()))
         } else {
             let mut v = Vec::new();
             for n in names.iter() {
-                if let Some((name, seq)) = n
+                if let Some(name) = n.strip_suffix(":*") {
+                    let conf = self
+                        .config
+                        .programs
+                        .get(name)
+                        .ok_or_else(|| RpcError::ProcessNotFound(n.to_owned()))?;
+                    (0..conf.numprocs).for_each(|seq| v.push(ProcessId::new(name.to_owned(), seq)));
+                } else if let Some((name, seq)) = n
                     .split_once(":")
                     .and_then(|(name, seq)| seq.parse::<u32>().map(|seq| (name, seq)).ok())
                 {
@@ -298,27 +1000,94 @@ impl Supervisor {
         }
     }
 
-    fn cleanup_processes(&mut self) {
-        let keys: Vec<ProcessId> = self.processes.iter().map(|(k, _)| k.to_owned()).collect();
+    fn is_getting_ready(&self, ids: &[ProcessId]) -> bool {
+        ids.iter()
+            .filter_map(|id| self.processes.get(id))
+            .any(|p| p.is_getting_ready())
+    }
 
-        for key in keys {
-            let _ = self.remove_process(&key);
+    fn check_ready(&self, id: &ProcessId) -> Result<(), RpcError> {
+        self.processes.get(id).map_or(Ok(()), |p| p.check_ready())
+    }
+
+    fn is_on_the_way(&self, ids: &[ProcessId], stopping: bool) -> bool {
+        ids.iter()
+            .filter_map(|id| self.processes.get(id))
+            .any(|p| p.settled(stopping).is_none())
+    }
+
+    fn settled_state(&self, id: &ProcessId, stopping: bool) -> Result<RpcOutput, RpcError> {
+        match self.processes.get(id) {
+            Some(p) => p.settled(stopping).unwrap_or_else(|| {
+                Err(RpcError::WaitTimeout(id.to_string(), p.state().to_string()))
+            }),
+            None => Err(RpcError::ProcessNotFound(id.to_string())),
         }
+    }
 
-        while self.trashes.len() != 0 {
+    // None while a batch of a rolling restart is still coming up: Starting or Backoff,
+    // and with wait_running until Running (Completed if oneshot), which keeps an unhealthy one
+    fn batch_settled(&self, ids: &[ProcessId], wait_running: bool) -> Option<Result<(), RpcError>> {
+        let mut settled = true;
+        for (id, process) in ids.iter().filter_map(|id| self.processes.get_key_value(id)) {
+            match process.state() {
+                ProcessState::Fatal | ProcessState::Stopped => {
+                    return Some(Err(RpcError::RestartAborted(id.to_string())))
+                }
+                ProcessState::Starting | ProcessState::Backoff => settled = false,
+                _ if wait_running => settled &= process.is_up(),
+                _ => {}
+            }
+        }
+        settled.then_some(Ok(()))
+    }
+
+    fn shutdown_deadline(&self) -> Instant {
+        Instant::now() + Duration::from_secs(self.config.general.shutdown_timeout)
+    }
+
+    fn wait_trashes(&mut self, deadline: Instant) {
+        while !self.trashes.is_empty() && Instant::now() < deadline {
             self.garbage_collect();
+            thread::sleep(Duration::from_millis(lib::EVENT_LOOP_TIME));
+        }
+    }
+
+    // the last started first
+    fn stop_order(&self) -> Vec<ProcessId> {
+        let mut keys: Vec<(Option<Instant>, ProcessId)> = self
+            .processes
+            .iter()
+            .map(|(k, p)| (p.started_at(), k.to_owned()))
+            .collect();
+        keys.sort_by_key(|k| std::cmp::Reverse(k.0));
+        keys.into_iter().map(|(_, key)| key).collect()
+    }
+
+    // whatever is still alive after shutdown_timeout. false if there was any
+    fn kill_trashes(&mut self) -> bool {
+        if self.trashes.is_empty() {
+            return true;
         }
+        LOG.warn(&format!(
+            "shutdown_timeout exceeded, kill {} processes",
+            self.trashes.len()
+        ));
+        self.trashes.iter_mut().for_each(|p| p.kill());
+        self.wait_trashes(Instant::now() + Duration::from_secs(1));
+        false
     }
 
     // Status(Vec<name>) -> Result( Vec<ProcessStatus>, Error)
     // where Error: ServiceError + ProcessNotFoundError
-    fn status(&self, words: SupvArg) -> RpcResponse {
+    fn status(&self, words: Vec<ProcessId>) -> RpcResponse {
         LOG.info("handle request - status");
-        LOG.info(&format!("{:?}", words));
+        LOG.debug(&format!("{:?}", words));
 
+        // a reload stops every process before it revives them, without the lock
         let v: Vec<ProcessStatus> = words
             .iter()
-            .map(|id| self.processes.get(id).unwrap().get_status())
+            .filter_map(|id| self.processes.get(id).map(|p| p.get_status()))
             .collect();
         RpcResponse::Status(v)
     }
@@ -1,25 +1,46 @@
-use core::ffi::c_char;
+mod child;
+mod health;
+mod notify;
+mod output;
+mod pidfd;
+mod ready;
+mod watch;
+
 use std::env::set_current_dir;
-use std::io::ErrorKind;
-use std::os::unix::process::CommandExt;
-use std::process::{Child, Command, Stdio};
+use std::fs::{remove_file, File};
+use std::io::{Read, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Command, Stdio};
 use std::time::Instant;
 
-use lib::config::{AutoRestart, ProcessConfig, ProgramConfig};
-use lib::logger::Logger;
+use lib::config::{AutoRestart, Overlap, ProcessConfig, ProgramConfig, ProgramType, StdinMode};
 use lib::logger::LOG;
+use lib::logger::{LogLevel, Logger};
 use lib::process_id::ProcessId;
 use lib::process_status::{ProcessState, ProcessStatus};
 use lib::response::{Error as RpcError, OutputMessage as RpcOutput};
+use lib::schedule::{epoch_seconds, LocalTime};
 
-use nix::libc::{dup2, getpwnam, getuid, open};
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use nix::libc::{getpwnam, getuid};
 use nix::sys::signal::{self, Signal};
 use nix::sys::stat::{umask, Mode};
 use nix::unistd::{setuid, Pid, Uid};
 
-use libc::{O_CREAT, O_TRUNC, O_WRONLY};
+use serde_json::json;
+
+use super::state::{from_epoch_millis, to_epoch_millis, ProcessSnapshot};
+pub use child::{reap, Child};
+use health::HealthCheck;
+use notify::NotifySocket;
+use output::Output;
+use pidfd::PidFd;
+use ready::Readiness;
+use watch::FileWatch;
 
-const INIT_DESCRIPTION: &'static str = "Not started";
+const INIT_DESCRIPTION: &str = "Not started";
 
 pub struct Process {
     pub proc: Option<Child>,
@@ -28,10 +49,38 @@ pub struct Process {
     current_try: u32,
     state: ProcessState,
     exit_status: Option<i32>,
+    exit_signal: Option<i32>,
     conf: ProcessConfig,
+    // the user it runs as, who has to own its pidfile and its main process
+    uid: Uid,
     start_at: Option<Instant>,
     stop_at: Option<Instant>,
     description: String,
+    stdout: Output,
+    stderr: Output,
+    captured: Vec<u8>,
+    health: Option<HealthCheck>,
+    readiness: Option<Readiness>,
+    notify: Option<NotifySocket>,
+    // what the process told through its notify socket
+    notify_status: Option<String>,
+    // MAINPID=, held like the daemon so that a reused pid is never signalled
+    main: Option<PidFd>,
+    watchdog_at: Option<Instant>,
+    // type=forking: the daemon the launcher left behind
+    daemon: Option<PidFd>,
+    // schedule=: the minute last looked at, and a run waiting for the current one
+    scheduled_minute: i64,
+    queued: bool,
+    last_run: Option<String>,
+    last_exit_code: Option<i32>,
+    // stopped because unhealthy, start again once it is down
+    restart_after_stop: bool,
+    // autostart waiting for depends_on
+    deferred: bool,
+    watch: Option<FileWatch>,
+    // states gone to since the last call of take_transitions, for wait
+    transitions: Vec<ProcessState>,
 }
 
 impl Process {
@@ -40,8 +89,21 @@ impl Process {
     }
 
     pub fn new(config: &ProgramConfig, index: u32) -> Result<Process, RpcError> {
-        let command = Process::new_command(config)?;
+        let uid = Process::get_uid(&config.user);
+        let umask = config.umask.unwrap_or(0o022);
+        let owner = config.user.as_ref().map(|_| Uid::from_raw(uid));
+        let mut command = Process::new_command(config, uid, umask)?;
         let id = ProcessId::new(config.name.to_owned(), index);
+        let id_string = id.to_string();
+        let readiness = Readiness::new(config);
+        let notify = Process::bind_notify_socket(&id, owner, &mut command);
+        let watch = FileWatch::new(&id_string, config);
+        if config.watchdog_secs > 0 {
+            command.env(
+                "WATCHDOG_USEC",
+                config.watchdog_secs.saturating_mul(1_000_000).to_string(),
+            );
+        }
         let process = Process {
             id,
             command,
@@ -51,14 +113,36 @@ impl Process {
             start_at: None,
             stop_at: None,
             exit_status: None,
+            exit_signal: None,
             description: String::from(INIT_DESCRIPTION),
             conf: ProcessConfig::from_program_config(config),
+            uid: Uid::from_raw(uid),
+            stdout: Output::new(&config.stdout_logfile, owner, umask),
+            stderr: Output::new(&config.stderr_logfile, owner, umask),
+            captured: Vec::new(),
+            health: HealthCheck::new(&id_string, config, uid, umask),
+            readiness,
+            notify,
+            notify_status: None,
+            main: None,
+            watchdog_at: None,
+            daemon: None,
+            // a schedule matching the minute taskmasterd starts in has passed already
+            scheduled_minute: epoch_seconds() / 60,
+            queued: false,
+            last_run: None,
+            last_exit_code: None,
+            restart_after_stop: false,
+            deferred: false,
+            watch,
+            transitions: Vec::new(),
         };
         Ok(process)
     }
 
     pub fn start(&mut self) -> Result<RpcOutput, RpcError> {
         let id = self.id.to_string();
+        self.deferred = false;
 
         if self.state.startable() {
             self.start_process()
@@ -70,10 +154,12 @@ impl Process {
 
     pub fn stop(&mut self) -> Result<RpcOutput, RpcError> {
         let id = self.id.to_string();
+        self.deferred = false;
 
         if self.state.stopable() {
+            self.restart_after_stop = false;
             self.stop_at = Some(Instant::now());
-            self.goto(ProcessState::Stopping, format!(""));
+            self.goto(ProcessState::Stopping, String::new());
             self.send_signal(self.conf.stopsignal)
                 .map(|_| RpcOutput::new(id.as_str(), "stopping"))
         } else {
@@ -81,6 +167,140 @@ impl Process {
         }
     }
 
+    pub fn signal(&mut self, signal: Signal) -> Result<RpcOutput, RpcError> {
+        let id = self.id.to_string();
+
+        if self.state.alive() {
+            self.send_signal(signal)
+                .map(|_| RpcOutput::new(id.as_str(), "signalled"))
+        } else {
+            Err(RpcError::ProcessNotRunning(id))
+        }
+    }
+
+    pub fn send_stdin(&mut self, text: &str) -> Result<RpcOutput, RpcError> {
+        let id = self.id.to_string();
+
+        if !self.state.alive() {
+            return Err(RpcError::ProcessNotRunning(id));
+        }
+        let stdin = self
+            .proc
+            .as_mut()
+            .and_then(|p| p.stdin.as_mut())
+            .ok_or_else(|| RpcError::ProcessNoStdin(id.to_owned()))?;
+
+        let mut line = text.to_owned();
+        line.push('\n');
+        stdin.write_all(line.as_bytes()).map_err(|e| {
+            LOG.warn(&format!("write to stdin of [{id}] failed - error={e}"));
+            RpcError::ProcessStdinError(id.to_owned())
+        })?;
+        Ok(RpcOutput::new(id.as_str(), "sent"))
+    }
+
+    // watch=: pause or resume restarting on changes
+    pub fn set_watch(&mut self, paused: bool) -> Result<RpcOutput, RpcError> {
+        let id = self.id.to_string();
+        let watch = self
+            .watch
+            .as_mut()
+            .ok_or_else(|| RpcError::ProcessNotWatched(id.to_owned()))?;
+
+        if paused {
+            watch.pause();
+            Ok(RpcOutput::new(id.as_str(), "watch paused"))
+        } else {
+            watch.resume();
+            Ok(RpcOutput::new(id.as_str(), "watch resumed"))
+        }
+    }
+
+    // last resort when a process outlives shutdown_timeout
+    pub fn kill(&mut self) {
+        if self.state.alive() {
+            self.send_signal(Signal::SIGKILL).unwrap_or_default();
+        }
+    }
+
+    // a critical process which exited or could not be started takes taskmasterd down
+    pub fn is_critical_failure(&self) -> bool {
+        self.conf.critical
+            && (self.state == ProcessState::Exited || self.state == ProcessState::Fatal)
+    }
+
+    // as a shell would report it: the exit code, 128 + signal if killed, 1 if it never ran
+    pub fn exit_code(&self) -> u8 {
+        match (self.exit_status, self.exit_signal) {
+            (Some(code), _) => code as u8,
+            (None, Some(signal)) => 128 + signal as u8,
+            (None, None) => 1,
+        }
+    }
+
+    pub fn started_at(&self) -> Option<Instant> {
+        self.start_at
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.state.alive()
+    }
+
+    pub fn state(&self) -> ProcessState {
+        self.state
+    }
+
+    pub fn defer_start(&mut self, dependencies: &[String]) {
+        self.deferred = true;
+        self.description = format!("Waiting for {}", dependencies.join(", "));
+    }
+
+    pub fn is_deferred(&self) -> bool {
+        self.deferred
+    }
+
+    // what depends_on waits for, a oneshot program has to be done
+    pub fn is_up(&self) -> bool {
+        match self.conf.program_type {
+            ProgramType::Oneshot => self.state == ProcessState::Completed,
+            _ => self.state == ProcessState::Running,
+        }
+    }
+
+    // a start of this process answers once it is ready or gave up
+    pub fn is_getting_ready(&self) -> bool {
+        self.readiness.is_some()
+            && (self.state == ProcessState::Starting || self.state == ProcessState::Backoff)
+    }
+
+    pub fn check_ready(&self) -> Result<(), RpcError> {
+        match self.state {
+            ProcessState::Running | ProcessState::Unhealthy => Ok(()),
+            _ if self.readiness.is_none() => Ok(()),
+            _ => Err(RpcError::ProcessNotReady(self.id.to_string())),
+        }
+    }
+
+    // --wait: None while on the way to what a start or stop aims for, then how it ended
+    pub fn settled(&self, stopping: bool) -> Option<Result<RpcOutput, RpcError>> {
+        let id = self.id.to_string();
+        let state = self.state.to_string();
+        match (self.state, stopping) {
+            (ProcessState::Starting | ProcessState::Backoff | ProcessState::Stopping, _) => None,
+            (ProcessState::Running | ProcessState::Unhealthy | ProcessState::Completed, false) => {
+                Some(Ok(RpcOutput::new(&id, &state)))
+            }
+            // stopwaitsecs expired
+            (ProcessState::Stopped, true) if self.exit_signal == Some(Signal::SIGKILL as i32) => {
+                Some(Ok(RpcOutput::new(&id, "Stopped (killed)")))
+            }
+            (ProcessState::Stopped | ProcessState::Exited | ProcessState::Fatal, true) => {
+                Some(Ok(RpcOutput::new(&id, &state)))
+            }
+            _ => Some(Err(RpcError::UnexpectedState(id, state))),
+        }
+    }
+
     pub fn is_stopped(&self) -> bool {
         self.state == ProcessState::Stopped
     }
@@ -89,70 +309,192 @@ impl Process {
         ProcessStatus::new(
             self.id.name.to_owned(),
             self.id.seq,
-            self.state.clone(),
+            self.state,
             self.description.to_string(),
         )
+        .with_notify_status(self.notify_status.clone().filter(|_| self.is_alive()))
+        .with_last_run(self.last_run.clone(), self.last_exit_code)
+    }
+
+    pub fn snapshot(&self) -> ProcessSnapshot {
+        let alive = self.proc.as_ref().filter(|_| self.is_alive());
+        ProcessSnapshot {
+            name: self.id.name.to_owned(),
+            seq: self.id.seq,
+            pid: alive.map(|p| p.id()),
+            state: self.state,
+            description: self.description.to_owned(),
+            started_at: self.start_at.map(to_epoch_millis),
+            current_try: self.current_try,
+            exit_status: self.exit_status,
+            main_pid: self
+                .daemon
+                .as_ref()
+                .or(self.main.as_ref())
+                .map(|p| p.pid().as_raw() as u32),
+            stdin: alive.and_then(|p| p.stdin.as_ref()).map(|s| s.as_raw_fd()),
+            stdout: alive.and(self.stdout.pipe_fd()),
+            stderr: alive.and(self.stderr.pipe_fd()),
+        }
+    }
+
+    // take over a process the previous taskmasterd was supervising
+    pub fn restore(&mut self, snapshot: ProcessSnapshot) -> std::io::Result<()> {
+        if let Some(fd) = snapshot.stdout {
+            self.stdout.adopt(fd)?;
+        }
+        if let Some(fd) = snapshot.stderr {
+            self.stderr.adopt(fd)?;
+        }
+        let stdin = snapshot.stdin.map(|fd| unsafe { File::from_raw_fd(fd) });
+        self.proc = snapshot.pid.map(|pid| Child::new(pid, stdin));
+        self.state = snapshot.state;
+        self.description = snapshot.description;
+        self.start_at = snapshot.started_at.map(from_epoch_millis);
+        self.current_try = snapshot.current_try;
+        self.exit_status = snapshot.exit_status;
+        let main = snapshot
+            .main_pid
+            .filter(|_| self.state.alive())
+            .and_then(|pid| PidFd::open(Pid::from_raw(pid as i32)).ok());
+        match self.conf.program_type {
+            ProgramType::Forking => self.daemon = main,
+            _ => self.main = main,
+        }
+        if self.state == ProcessState::Stopping {
+            self.stop_at = Some(Instant::now());
+        }
+        LOG.info(&format!(
+            "[{}] adopted - state={}, pid={:?}",
+            self.id, self.state, snapshot.pid
+        ));
+        Ok(())
+    }
+
+    pub fn reopen_logs(&mut self) -> std::io::Result<()> {
+        self.stdout.reopen_logfile()?;
+        self.stderr.reopen_logfile()
+    }
+
+    // output of the child since the last call, for attached clients
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.captured)
+    }
+
+    pub fn take_transitions(&mut self) -> Vec<ProcessState> {
+        std::mem::take(&mut self.transitions)
     }
 
     pub fn run(&mut self) -> Result<(), RpcError> {
+        let seen = self.captured.len();
+        self.stdout.take_captured(&mut self.captured);
+        self.stderr.take_captured(&mut self.captured);
+        if let Some(readiness) = self.readiness.as_mut() {
+            readiness.feed(&self.captured[seen..]);
+        }
+        self.read_notify_socket();
+        self.run_schedule();
+        self.run_watch();
+
         match self.state {
-            ProcessState::Starting => self.starting(),
+            ProcessState::Starting => self.starting()?,
             ProcessState::Running => self.running(),
+            ProcessState::Unhealthy => self.unhealthy(),
             ProcessState::Backoff => self.backoff()?,
             ProcessState::Stopping => self.stopping()?,
             ProcessState::Stopped => self.stopped(),
             ProcessState::Exited => self.exited()?,
+            ProcessState::Completed => self.completed(),
             ProcessState::Fatal => self.fatal(),
             ProcessState::Unknown => panic!("invalid process state"),
         }
+        self.watchdog();
         Ok(())
     }
 }
 
 impl Process {
-    fn new_command(conf: &ProgramConfig) -> Result<Command, RpcError> {
-        let mut stdout_path = conf.stdout_logfile.to_owned();
-        let mut stderr_path = conf.stderr_logfile.to_owned();
-
-        stderr_path.push('\0');
-        stdout_path.push('\0');
-
-        let v_uid = Process::get_uid(&conf.user);
-        let v_umask = conf.umask.unwrap_or(0o022);
+    fn new_command(conf: &ProgramConfig, v_uid: u32, v_umask: u32) -> Result<Command, RpcError> {
         let directory = conf.directory.clone();
 
         let mut cmd = Command::new(&conf.command[0]);
 
         cmd.args(&conf.command[1..])
             .envs(&conf.environment)
-            .stdin(Stdio::null());
+            .stdin(match conf.stdin {
+                StdinMode::Null => Stdio::null(),
+                StdinMode::Pipe => Stdio::piped(),
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
 
         unsafe {
             cmd.pre_exec(move || {
                 setuid(Uid::from_raw(v_uid))?;
                 umask(Mode::from_bits_truncate(v_umask));
-                let stderr_ptr = stderr_path.as_ptr() as *const c_char;
-                let stdout_ptr = stdout_path.as_ptr() as *const c_char;
-                let stderr = open(stderr_ptr, O_WRONLY | O_TRUNC | O_CREAT, 0o777);
-                let stdout = open(stdout_ptr, O_WRONLY | O_TRUNC | O_CREAT, 0o777);
-
-                if stdout < 0 || stderr < 0 || dup2(stderr, 2) < 0 || dup2(stdout, 1) < 0 {
-                    LOG.crit(&format!(
-                        "setting logfile failed: {stdout_path}: {stdout}, {stderr_path}: {stderr}"
-                    ));
-                    return Err(std::io::Error::new(
-                        ErrorKind::Other,
-                        format!("can not spawn"),
-                    ));
-                }
-                set_current_dir(directory.to_owned())
+                set_current_dir(&directory)
             });
         }
         Ok(cmd)
     }
 
+    fn bind_notify_socket(
+        id: &ProcessId,
+        owner: Option<Uid>,
+        command: &mut Command,
+    ) -> Option<NotifySocket> {
+        match NotifySocket::bind(id, owner) {
+            Ok(socket) => {
+                command.env("NOTIFY_SOCKET", socket.path());
+                Some(socket)
+            }
+            Err(e) => {
+                LOG.warn(&format!("[{id}] failed to bind notify socket - {e}"));
+                None
+            }
+        }
+    }
+
+    fn read_notify_socket(&mut self) {
+        let Some(socket) = self.notify.as_ref() else {
+            return;
+        };
+        for (key, value) in socket.recv() {
+            match (key.as_str(), value.as_str()) {
+                ("READY", "1") => {
+                    if let Some(readiness) = self.readiness.as_mut() {
+                        readiness.notified();
+                    }
+                }
+                ("STATUS", text) => self.notify_status = Some(text.to_owned()),
+                ("MAINPID", pid) => self.set_main_pid(pid),
+                ("WATCHDOG", "1") => self.watchdog_at = Some(Instant::now()),
+                _ => {}
+            }
+        }
+    }
+
+    // anyone who can write to the socket may send MAINPID=, only a process running as
+    // the program's user is taken
+    fn set_main_pid(&mut self, pid: &str) {
+        if self.proc.is_none() {
+            return;
+        }
+        let main = pid
+            .parse()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+            .and_then(|pid| PidFd::open_owned_by(Pid::from_raw(pid), self.uid));
+        match main {
+            Ok(main) => {
+                LOG.info(&format!("[{}] main pid is {pid}", self.id));
+                self.main = Some(main);
+            }
+            Err(e) => LOG.warn(&format!("[{}] ignore MAINPID={pid} - {e}", self.id)),
+        }
+    }
+
     fn get_uid(user_name: &Option<String>) -> u32 {
-        if let None = user_name {
+        if user_name.is_none() {
             return unsafe { getuid() };
         }
         let mut user_name = user_name.as_ref().unwrap().to_string();
@@ -171,29 +513,105 @@ impl Process {
     }
 
     fn start_process(&mut self) -> Result<(), RpcError> {
+        self.remove_stale_pidfile();
         self.spawn_process()?;
         self.start_at = Some(Instant::now());
-        self.goto(ProcessState::Starting, format!(""));
+        if let Some(health) = self.health.as_mut() {
+            health.reset();
+        }
+        if let Some(readiness) = self.readiness.as_mut() {
+            readiness.reset();
+        }
+        self.notify_status = None;
+        self.main = None;
+        self.watchdog_at = None;
+        self.daemon = None;
+        self.goto(ProcessState::Starting, String::new());
         Ok(())
     }
 
     fn spawn_process(&mut self) -> Result<(), RpcError> {
-        let proc = self.command.spawn();
+        let proc = self
+            .stdout
+            .open_logfile()
+            .and_then(|_| self.stderr.open_logfile())
+            .and_then(|_| self.command.spawn());
+
+        let mut proc = match proc {
+            Ok(proc) => proc,
+            Err(e) => {
+                self.goto(ProcessState::Fatal, format!("spawn failed - error={}", e));
+                return Err(RpcError::spawn(e.to_string().as_str()));
+            }
+        };
 
-        if let Err(e) = proc {
-            self.goto(ProcessState::Fatal, format!("spawn failed - error={}", e));
-            return Err(RpcError::spawn(e.to_string().as_str()));
+        if let Some(stdout) = proc.stdout.take() {
+            self.stdout.set_pipe(stdout);
         }
-
-        self.proc = proc.ok();
+        if let Some(stderr) = proc.stderr.take() {
+            self.stderr.set_pipe(stderr);
+        }
+        let stdin = proc.stdin.take().map(|s| File::from(OwnedFd::from(s)));
+        self.proc = Some(Child::new(proc.id(), stdin));
+        self.set_stdin_nonblocking();
         Ok(())
     }
 
+    // a child which does not read its stdin must not block the event loop
+    fn set_stdin_nonblocking(&self) {
+        if let Some(stdin) = self.proc.as_ref().and_then(|p| p.stdin.as_ref()) {
+            if let Err(e) = fcntl(stdin.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_NONBLOCK)) {
+                LOG.warn(&format!(
+                    "set stdin of [{}] nonblocking failed - {e}",
+                    self.id
+                ));
+            }
+        }
+    }
+
+    // the pid a process reported with MAINPID=, or the one taskmasterd spawned.
+    // None for one restored without a pid, e.g. in backoff between two tries
+    fn pid(&self) -> Option<u32> {
+        match self.daemon.as_ref().or(self.main.as_ref()) {
+            Some(main) => Some(main.pid().as_raw() as u32),
+            None => self.proc.as_ref().map(|p| p.id()),
+        }
+    }
+
     fn send_signal(&mut self, signal: Signal) -> Result<(), RpcError> {
-        let proc = self.proc.as_ref().unwrap();
-        let pid = Pid::from_raw(proc.id() as i32);
-        LOG.info(&format!("send {signal} to [{}]", self.id));
-        signal::kill(pid, signal).map_err(|_| RpcError::ProcessNotFound(self.id.name.to_owned()))
+        let Some(target) = self.pid() else {
+            return Err(RpcError::ProcessNotRunning(self.id.to_string()));
+        };
+        LOG.log_with(
+            LogLevel::Info,
+            &format!("send {signal} to [{}]", self.id),
+            &[
+                ("program", json!(self.id.name)),
+                ("seq", json!(self.id.seq)),
+                ("pid", json!(target)),
+            ],
+        );
+        // the launcher is gone, the daemon is what runs
+        if let Some(daemon) = self.daemon.as_ref() {
+            return daemon
+                .send_signal(signal)
+                .map_err(|_| RpcError::ProcessNotFound(self.id.name.to_owned()));
+        }
+        let not_found = || RpcError::ProcessNotFound(self.id.name.to_owned());
+        // restored with its MAINPID only, which self.pid() found
+        let Some(pid) = self.proc.as_ref().map(|p| p.pid()) else {
+            return self
+                .main
+                .as_ref()
+                .unwrap()
+                .send_signal(signal)
+                .map_err(|_| not_found());
+        };
+        // the spawned process may only be a launcher for the main one
+        if let Some(main) = self.main.as_ref().filter(|p| p.pid() != pid) {
+            main.send_signal(signal).unwrap_or_default();
+        }
+        signal::kill(pid, signal).map_err(|_| not_found())
     }
 
     fn autorestart(&mut self) -> Result<(), RpcError> {
@@ -204,34 +622,154 @@ impl Process {
     }
 
     fn is_process_alive(&mut self) -> bool {
-        match self.proc.as_mut().unwrap().try_wait() {
+        // not our child, so how it exited is unknown
+        if let Some(daemon) = self.daemon.as_ref() {
+            if !daemon.exited() {
+                return true;
+            }
+            self.exit_status = None;
+            self.exit_signal = None;
+            return false;
+        }
+        let Some(proc) = self.proc.as_mut() else {
+            return self.main.as_ref().is_some_and(|main| !main.exited());
+        };
+        match proc.try_wait() {
             // alive
             Ok(None) => true,
             // died
             Ok(Some(status)) => {
                 self.exit_status = status.code();
+                self.exit_signal = status.signal();
+                if self.conf.schedule.is_some() {
+                    self.last_exit_code = Some(self.exit_code() as i32);
+                }
                 false
             }
+            // not our child anymore (e.g. ECHILD), waiting for it would never end
             Err(e) => {
-                LOG.crit(&format!("{e}"));
-                true
+                LOG.crit(&format!("[{}] wait failed - {e}", self.id));
+                false
             }
         }
     }
 
-    fn starting(&mut self) {
+    fn start_timed_out(&self) -> bool {
+        let running_millis = self.start_at.unwrap().elapsed().as_millis() as u64;
+        running_millis > self.conf.startsecs * 1000 - lib::EVENT_LOOP_TIME
+    }
+
+    // type=forking: the launcher has to exit successfully and leave the daemon behind,
+    // known from MAINPID= or the pidfile. startsecs counts from the launch
+    fn launching(&mut self) -> Result<(), RpcError> {
+        let launcher_alive = self.is_process_alive();
+        if !launcher_alive && self.exit_status != Some(0) {
+            self.goto(ProcessState::Backoff, "Exited too quickly.".to_string());
+            self.current_try += 1;
+            return Ok(());
+        }
+        if !launcher_alive {
+            if let Some(daemon) = self.find_daemon() {
+                LOG.info(&format!("[{}] daemon pid is {}", self.id, daemon.pid()));
+                self.daemon = Some(daemon);
+                return Ok(());
+            }
+        }
+        if self.start_timed_out() {
+            if launcher_alive {
+                if let Err(e) = self.send_signal(Signal::SIGKILL) {
+                    LOG.warn(&format!("[{}] kill failed - {e}", self.id));
+                }
+            }
+            self.goto(ProcessState::Backoff, "No daemon in time.".to_string());
+            self.current_try += 1;
+        }
+        Ok(())
+    }
+
+    // the pidfile was removed before the launch and is only read once the launcher
+    // exited successfully, so what it names is the daemon, as long as no one but the
+    // program's user wrote it
+    fn find_daemon(&mut self) -> Option<PidFd> {
+        if let Some(main) = self.main.take() {
+            return Some(main);
+        }
+        let path = self.conf.pidfile.as_ref()?;
+        match self.read_pidfile(path) {
+            Ok(daemon) => Some(daemon),
+            // not written yet, or by someone else
+            Err(e) => {
+                LOG.debug(&format!("[{}] no daemon from pidfile - {e}", self.id));
+                None
+            }
+        }
+    }
+
+    fn read_pidfile(&self, path: &str) -> std::io::Result<PidFd> {
+        let mut file = File::open(path)?;
+        let owner = Uid::from_raw(file.metadata()?.uid());
+        if owner != self.uid {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("{path} is owned by uid {owner}"),
+            ));
+        }
+        let mut pid = String::new();
+        file.read_to_string(&mut pid)?;
+        let pid = pid
+            .trim()
+            .parse()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        PidFd::open_owned_by(Pid::from_raw(pid), self.uid)
+    }
+
+    // a pidfile left by an earlier run must not be taken for the new daemon's
+    fn remove_stale_pidfile(&self) {
+        let Some(path) = self.conf.pidfile.as_ref() else {
+            return;
+        };
+        match remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                LOG.warn(&format!("[{}] failed to remove {path} - {e}", self.id))
+            }
+            _ => {}
+        }
+    }
+
+    fn starting(&mut self) -> Result<(), RpcError> {
+        if self.conf.program_type == ProgramType::Forking && self.daemon.is_none() {
+            return self.launching();
+        }
         if self.is_process_alive() {
-            let running_millis = self.start_at.unwrap().elapsed().as_millis() as u64;
-            if running_millis > self.conf.startsecs * 1000 - lib::EVENT_LOOP_TIME {
+            let timed_out = self.start_timed_out();
+            // with a readiness check, startsecs is how long it may take to get ready
+            let ready = match self.readiness.as_mut() {
+                Some(readiness) => readiness.poll(),
+                None => timed_out,
+            };
+            if ready {
                 self.goto(
                     ProcessState::Running,
-                    format!("pid {}, uptime 0:00:00", self.proc.as_ref().unwrap().id(),),
+                    format!("pid {}, uptime 0:00:00", self.pid().unwrap_or_default()),
                 );
+            } else if timed_out {
+                // backoff waits until it is gone, or retries if it already is
+                if let Err(e) = self.send_signal(Signal::SIGKILL) {
+                    LOG.warn(&format!("[{}] kill failed - {e}", self.id));
+                }
+                self.goto(ProcessState::Backoff, "Not ready in time.".to_string());
+                self.current_try += 1;
             }
+        } else if self.conf.program_type == ProgramType::Oneshot && self.exited_expectedly() {
+            self.goto(ProcessState::Completed, Logger::get_formated_timestamp());
+        } else if self.conf.schedule.is_some() && self.exited_expectedly() {
+            // a scheduled job is done once it exits, however quickly
+            self.goto(ProcessState::Exited, Logger::get_formated_timestamp());
         } else {
-            self.goto(ProcessState::Backoff, format!("Exited too quickly."));
+            self.goto(ProcessState::Backoff, "Exited too quickly.".to_string());
             self.current_try += 1;
         }
+        Ok(())
     }
 
     fn running(&mut self) {
@@ -241,7 +779,18 @@ impl Process {
             let mins = (running_secs % 3600) / 60;
             let secs = running_secs % 60;
             let time = format!("{}:{:02}:{:02}", hours, mins, secs);
-            self.description = format!("pid {}, uptime {}", self.proc.as_ref().unwrap().id(), time);
+            let description = format!("pid {}, uptime {}", self.pid().unwrap_or_default(), time);
+            match (self.state, self.health.as_mut().and_then(|h| h.poll())) {
+                (ProcessState::Running, Some(false)) => {
+                    self.goto(ProcessState::Unhealthy, description)
+                }
+                (ProcessState::Unhealthy, Some(true)) => {
+                    self.goto(ProcessState::Running, description)
+                }
+                _ => self.description = description,
+            }
+        } else if self.conf.program_type == ProgramType::Oneshot && self.exited_expectedly() {
+            self.goto(ProcessState::Completed, Logger::get_formated_timestamp());
         } else {
             let unexpected = match self.exit_status {
                 Some(ref code) if !self.conf.exitcodes.contains(code) => {
@@ -254,7 +803,46 @@ impl Process {
         }
     }
 
+    fn unhealthy(&mut self) {
+        if self.conf.autorestart == AutoRestart::Never {
+            self.running();
+            return;
+        }
+        LOG.warn(&format!("[{}] unhealthy, restarting", self.id));
+        if let Err(e) = self.stop_and_restart() {
+            LOG.warn(&format!("[{}] restart failed - {e}", self.id));
+        }
+    }
+
+    // restart a process which stopped pinging WATCHDOG=1 for watchdog_secs
+    fn watchdog(&mut self) {
+        if self.conf.watchdog_secs == 0
+            || (self.state != ProcessState::Running && self.state != ProcessState::Unhealthy)
+        {
+            return;
+        }
+        let last = self.watchdog_at.or(self.start_at).unwrap();
+        if last.elapsed().as_secs() < self.conf.watchdog_secs {
+            return;
+        }
+        LOG.warn(&format!("[{}] watchdog timeout, restarting", self.id));
+        if let Err(e) = self.stop_and_restart() {
+            LOG.warn(&format!("[{}] restart failed - {e}", self.id));
+        }
+    }
+
+    fn stop_and_restart(&mut self) -> Result<(), RpcError> {
+        let stopped = self.stop();
+        // also when it was gone before the signal, stopping starts it once it is reaped
+        self.restart_after_stop = self.state == ProcessState::Stopping;
+        stopped.map(|_| ())
+    }
+
     fn backoff(&mut self) -> Result<(), RpcError> {
+        // killed because it never got ready, wait until it is gone
+        if self.proc.is_some() && self.is_process_alive() {
+            return Ok(());
+        }
         if self.conf.startretries < self.current_try {
             self.goto(ProcessState::Fatal, self.description.clone());
         } else {
@@ -271,6 +859,13 @@ impl Process {
             }
         } else {
             self.goto(ProcessState::Stopped, Logger::get_formated_timestamp());
+            if self.restart_after_stop {
+                self.restart_after_stop = false;
+                // a failed spawn leaves it Fatal, taskmasterd goes on
+                if let Err(e) = self.start_process() {
+                    LOG.warn(&format!("[{}] restart failed - {e}", self.id));
+                }
+            }
         }
         Ok(())
     }
@@ -279,6 +874,11 @@ impl Process {
         let exitcodes = &self.conf.exitcodes;
         let autorestart = self.conf.autorestart;
 
+        // a scheduled job which is done waits for its next run
+        if self.conf.schedule.is_some() && self.exited_expectedly() {
+            return Ok(());
+        }
+
         match autorestart {
             AutoRestart::Always => self.autorestart()?,
             AutoRestart::Unexpected => match self.exit_status {
@@ -292,18 +892,103 @@ impl Process {
         Ok(())
     }
 
+    fn exited_expectedly(&self) -> bool {
+        matches!(self.exit_status, Some(ref code) if self.conf.exitcodes.contains(code))
+    }
+
+    // schedule=: start at the matching minutes. a run due while the previous one
+    // is still going is skipped or queued according to schedule_overlap
+    fn run_schedule(&mut self) {
+        let Some(schedule) = self.conf.schedule.as_ref() else {
+            return;
+        };
+        let idle = !self.state.alive() && self.state != ProcessState::Backoff;
+        let seconds = epoch_seconds();
+
+        let due =
+            seconds / 60 != self.scheduled_minute && schedule.matches(&LocalTime::at(seconds));
+        self.scheduled_minute = seconds / 60;
+        if idle && (due || self.queued) {
+            self.queued = false;
+            // a failed spawn leaves it Fatal until the next run, taskmasterd goes on
+            if let Err(e) = self.start_scheduled() {
+                LOG.warn(&format!("[{}] scheduled run failed - {e}", self.id));
+            }
+            return;
+        }
+        if due {
+            match self.conf.schedule_overlap {
+                Overlap::Skip => {
+                    LOG.warn(&format!("[{}] still running, skip scheduled run", self.id))
+                }
+                Overlap::Queue => {
+                    LOG.info(&format!("[{}] still running, queue scheduled run", self.id));
+                    self.queued = true;
+                }
+            }
+        }
+    }
+
+    // watch=: restart once the watched files changed. a process stopped by hand stays stopped
+    fn run_watch(&mut self) {
+        if !self.watch.as_mut().is_some_and(|w| w.poll()) {
+            return;
+        }
+        let result = match self.state {
+            ProcessState::Starting | ProcessState::Running | ProcessState::Unhealthy => {
+                LOG.info(&format!("[{}] watched files changed, restarting", self.id));
+                self.stop_and_restart()
+            }
+            ProcessState::Exited | ProcessState::Completed | ProcessState::Fatal => {
+                LOG.info(&format!("[{}] watched files changed, starting", self.id));
+                self.current_try = 1;
+                self.exit_status = None;
+                self.start_process()
+            }
+            // retrying anyway, the change gets it all its retries back
+            ProcessState::Backoff => {
+                self.current_try = 1;
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+        // a failed spawn leaves it Fatal until the next change, taskmasterd goes on
+        if let Err(e) = result {
+            LOG.warn(&format!("[{}] restart on change failed - {e}", self.id));
+        }
+    }
+
+    fn start_scheduled(&mut self) -> Result<(), RpcError> {
+        LOG.info(&format!("[{}] scheduled run", self.id));
+        self.current_try = 1;
+        self.exit_status = None;
+        self.last_run = Some(Logger::get_formated_timestamp());
+        self.last_exit_code = None;
+        self.start_process()
+    }
+
     fn stopped(&mut self) {}
 
+    fn completed(&mut self) {}
+
     fn fatal(&mut self) {}
 
     fn goto(&mut self, state: ProcessState, description: String) {
-        LOG.info(&format!(
-            "[{}] state goes to {}",
-            self.id,
-            state.to_string()
-        ));
+        LOG.log_with(
+            LogLevel::Info,
+            &format!("[{}] state goes to {}", self.id, state),
+            &[
+                ("program", json!(self.id.name)),
+                ("seq", json!(self.id.seq)),
+                ("from_state", json!(self.state.to_string())),
+                ("to_state", json!(state.to_string())),
+                ("pid", json!(self.proc.as_ref().map(|p| p.id()))),
+                ("exit_code", json!(self.exit_status)),
+            ],
+        );
 
         self.state = state;
         self.description = description;
+        self.transitions.push(state);
     }
 }
@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::sync::Mutex;
+
+use lib::logger::LOG;
+
+use nix::unistd::Pid;
+
+// pid of every Child -> its exit status once reap() collected it
+static CHILDREN: Mutex<BTreeMap<i32, Option<ExitStatus>>> = Mutex::new(BTreeMap::new());
+
+fn children() -> std::sync::MutexGuard<'static, BTreeMap<i32, Option<ExitStatus>>> {
+    CHILDREN.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// init mode: wait for any child. the status of a Child is kept for its try_wait,
+// anything else is an orphan which only has to be cleaned up
+pub fn reap() {
+    loop {
+        let mut status = 0;
+        let pid = unsafe { libc::waitpid(-1, &mut status, libc::WNOHANG) };
+        if pid <= 0 {
+            break;
+        }
+        match children().get_mut(&pid) {
+            Some(slot) => *slot = Some(ExitStatus::from_raw(status)),
+            None => LOG.debug(&format!("reaped orphan - pid={pid}")),
+        }
+    }
+}
+
+// a child known only by its pid, so that a re-executed taskmasterd can adopt it
+pub struct Child {
+    pid: Pid,
+    status: Option<ExitStatus>,
+    pub stdin: Option<File>,
+}
+
+impl Child {
+    pub fn new(pid: u32, stdin: Option<File>) -> Self {
+        children().insert(pid as i32, None);
+        Child {
+            pid: Pid::from_raw(pid as i32),
+            status: None,
+            stdin,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.pid.as_raw() as u32
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        if let Some(status) = self.status {
+            return Ok(Some(status));
+        }
+        // reaped already, the lock must be released before exited() takes it again
+        let reaped = children().get(&self.pid.as_raw()).copied().flatten();
+        if let Some(status) = reaped {
+            self.exited(status);
+            return Ok(Some(status));
+        }
+
+        let mut status = 0;
+        match unsafe { libc::waitpid(self.pid.as_raw(), &mut status, libc::WNOHANG) } {
+            0 => Ok(None),
+            -1 => Err(io::Error::last_os_error()),
+            _ => {
+                let status = ExitStatus::from_raw(status);
+                self.exited(status);
+                Ok(Some(status))
+            }
+        }
+    }
+
+    fn exited(&mut self, status: ExitStatus) {
+        self.status = Some(status);
+        children().remove(&self.pid.as_raw());
+    }
+}
+
+impl Drop for Child {
+    fn drop(&mut self) {
+        if self.status.is_none() {
+            children().remove(&self.pid.as_raw());
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use lib::config::{HealthProbe, ProgramConfig};
+use lib::logger::LOG;
+
+use nix::sys::signal::{self, Signal};
+use nix::sys::stat::{umask, Mode};
+use nix::unistd::{setuid, Uid};
+
+use super::child::Child;
+
+type ProbeResult = Result<(), String>;
+
+enum Probe {
+    // spawned from the event loop, so that init mode reaping knows about it
+    Command { child: Child, deadline: Instant },
+    // timed out, waiting to be reaped before the next probe starts
+    Killed(Child),
+    // tcp and http probes block, they run in their own thread
+    Socket(Receiver<ProbeResult>),
+}
+
+pub struct HealthCheck {
+    name: String,
+    kind: HealthProbe,
+    directory: String,
+    uid: u32,
+    umask: u32,
+    interval: Duration,
+    timeout: Duration,
+    retries: u32,
+    failures: u32,
+    next_at: Instant,
+    probe: Option<Probe>,
+}
+
+impl HealthCheck {
+    pub fn new(name: &str, conf: &ProgramConfig, uid: u32, umask: u32) -> Option<Self> {
+        let kind = conf.healthcheck.clone()?;
+        Some(HealthCheck {
+            name: name.to_owned(),
+            kind,
+            directory: conf.directory.to_owned(),
+            uid,
+            umask,
+            interval: Duration::from_secs(conf.healthcheck_interval),
+            timeout: Duration::from_secs(conf.healthcheck_timeout),
+            retries: conf.healthcheck_retries,
+            failures: 0,
+            next_at: Instant::now(),
+            probe: None,
+        })
+    }
+
+    // start over for a new run of the process, the first probe goes right away
+    pub fn reset(&mut self) {
+        self.failures = 0;
+        self.next_at = Instant::now();
+        match self.probe.take() {
+            Some(Probe::Command { child, .. }) => self.kill(child),
+            Some(Probe::Killed(child)) => self.probe = Some(Probe::Killed(child)),
+            _ => {}
+        }
+    }
+
+    // Some(true) when a probe passed, Some(false) when `retries` probes failed in a row
+    pub fn poll(&mut self) -> Option<bool> {
+        let result = match self.probe.take() {
+            Some(probe) => self.finish(probe)?,
+            None if Instant::now() >= self.next_at => match self.start() {
+                Ok(probe) => {
+                    self.probe = Some(probe);
+                    return None;
+                }
+                Err(e) => Err(e),
+            },
+            None => return None,
+        };
+
+        self.next_at = Instant::now() + self.interval;
+        match result {
+            Ok(()) => {
+                self.failures = 0;
+                Some(true)
+            }
+            Err(e) => {
+                self.failures += 1;
+                LOG.warn(&format!(
+                    "[{}] health check failed ({}/{}) - {e}",
+                    self.name, self.failures, self.retries
+                ));
+                (self.failures >= self.retries).then_some(false)
+            }
+        }
+    }
+
+    fn start(&self) -> Result<Probe, String> {
+        let timeout = self.timeout;
+        let (tx, rx) = channel();
+
+        match self.kind.clone() {
+            HealthProbe::Command(cmd) => {
+                let (uid, mask) = (self.uid, self.umask);
+                let mut command = Command::new(&cmd[0]);
+                command
+                    .args(&cmd[1..])
+                    .current_dir(&self.directory)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null());
+                // the check runs with no more privileges than the program it checks
+                unsafe {
+                    command.pre_exec(move || {
+                        setuid(Uid::from_raw(uid))?;
+                        umask(Mode::from_bits_truncate(mask));
+                        Ok(())
+                    });
+                }
+                let child = command.spawn().map_err(|e| format!("spawn failed - {e}"))?;
+                return Ok(Probe::Command {
+                    child: Child::new(child.id(), None),
+                    deadline: Instant::now() + timeout,
+                });
+            }
+            HealthProbe::Tcp(addr) => thread::spawn(move || tx.send(probe_tcp(&addr, timeout))),
+            HealthProbe::Http(url) => thread::spawn(move || tx.send(probe_http(&url, timeout))),
+        };
+        Ok(Probe::Socket(rx))
+    }
+
+    // None while the probe is still running
+    fn finish(&mut self, probe: Probe) -> Option<ProbeResult> {
+        match probe {
+            Probe::Command {
+                mut child,
+                deadline,
+            } => match child.try_wait() {
+                Ok(Some(status)) if status.success() => Some(Ok(())),
+                Ok(Some(status)) => Some(Err(format!("command {status}"))),
+                Ok(None) if Instant::now() < deadline => {
+                    self.probe = Some(Probe::Command { child, deadline });
+                    None
+                }
+                Ok(None) => {
+                    self.kill(child);
+                    Some(Err("command timed out".to_owned()))
+                }
+                Err(e) => Some(Err(format!("wait failed - {e}"))),
+            },
+            Probe::Killed(mut child) => {
+                if let Ok(None) = child.try_wait() {
+                    self.probe = Some(Probe::Killed(child));
+                }
+                None
+            }
+            Probe::Socket(rx) => match rx.try_recv() {
+                Ok(result) => Some(result),
+                Err(TryRecvError::Empty) => {
+                    self.probe = Some(Probe::Socket(rx));
+                    None
+                }
+                Err(TryRecvError::Disconnected) => Some(Err("probe aborted".to_owned())),
+            },
+        }
+    }
+
+    fn kill(&mut self, child: Child) {
+        signal::kill(child.pid(), Signal::SIGKILL).unwrap_or_default();
+        self.probe = Some(Probe::Killed(child));
+    }
+}
+
+pub fn connect(addr: &str, timeout: Duration) -> Result<TcpStream, String> {
+    let addrs = addr.to_socket_addrs().map_err(|e| format!("{addr}: {e}"))?;
+    for a in addrs {
+        if let Ok(stream) = TcpStream::connect_timeout(&a, timeout) {
+            return Ok(stream);
+        }
+    }
+    Err(format!("{addr}: connection failed"))
+}
+
+fn probe_tcp(addr: &str, timeout: Duration) -> ProbeResult {
+    connect(addr, timeout).map(|_| ())
+}
+
+// http://host[:port][/path], any 2xx or 3xx answer is healthy
+fn probe_http(url: &str, timeout: Duration) -> ProbeResult {
+    let rest = url.strip_prefix("http://").unwrap_or(url);
+    let (host, path) = match rest.split_once('/') {
+        Some((host, path)) => (host, format!("/{path}")),
+        None => (rest, "/".to_owned()),
+    };
+    let addr = match host.contains(':') {
+        true => host.to_owned(),
+        false => format!("{host}:80"),
+    };
+
+    let mut stream = connect(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout)).unwrap_or_default();
+    stream.set_write_timeout(Some(timeout)).unwrap_or_default();
+
+    let request = format!("GET {path} HTTP/1.0\r\nHost: {host}\r\nConnection: close\r\n\r\n");
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| format!("{url}: {e}"))?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut status_line)
+        .map_err(|e| format!("{url}: {e}"))?;
+
+    match status_line.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') || code.starts_with('3') => Ok(()),
+        _ => Err(format!("{url}: {}", status_line.trim())),
+    }
+}
@@ -0,0 +1,72 @@
+use std::env::temp_dir;
+use std::fs::{metadata, remove_file};
+use std::io::{self, ErrorKind};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+
+use lib::process_id::ProcessId;
+
+use nix::unistd::{chown, getpid, Uid};
+
+const DATAGRAM_SIZE: usize = 4096;
+
+// the NOTIFY_SOCKET of one process, it speaks systemd's sd_notify protocol:
+// datagrams of newline separated KEY=VALUE pairs
+pub struct NotifySocket {
+    path: PathBuf,
+    inode: u64,
+    socket: UnixDatagram,
+}
+
+impl NotifySocket {
+    // named after taskmasterd's pid, which a reexec keeps, so an adopted
+    // process still reaches the socket its environment points to.
+    // sending takes write permission, a program with user= owns its socket
+    pub fn bind(id: &ProcessId, owner: Option<Uid>) -> io::Result<Self> {
+        let path = temp_dir().join(format!("taskmasterd-{}-{}.notify", getpid(), id));
+        remove_file(&path).unwrap_or_default();
+        let socket = UnixDatagram::bind(&path)?;
+        socket.set_nonblocking(true)?;
+        if let Some(owner) = owner {
+            chown(&path, Some(owner), None)?;
+        }
+        let inode = metadata(&path)?.ino();
+        Ok(NotifySocket {
+            path,
+            inode,
+            socket,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // every KEY=VALUE pair received since the last call
+    pub fn recv(&self) -> Vec<(String, String)> {
+        let mut buf = [0u8; DATAGRAM_SIZE];
+        let mut pairs = Vec::new();
+
+        loop {
+            match self.socket.recv(&mut buf) {
+                Ok(n) => String::from_utf8_lossy(&buf[..n])
+                    .lines()
+                    .filter_map(|l| l.split_once('='))
+                    .for_each(|(k, v)| pairs.push((k.to_owned(), v.to_owned()))),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+        }
+        pairs
+    }
+}
+
+impl Drop for NotifySocket {
+    fn drop(&mut self) {
+        // a restarted process may have bound the same path already
+        if metadata(&self.path).is_ok_and(|m| m.ino() == self.inode) {
+            remove_file(&self.path).unwrap_or_default();
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::fs::{File, OpenOptions, Permissions};
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+
+use lib::logger::LOG;
+
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use nix::unistd::{fchown, Uid};
+
+const PIPE_BUF_SIZE: usize = 4096;
+
+// where the pump thread puts what the child writes
+#[derive(Default)]
+struct Sink {
+    logfile: Option<File>,
+    captured: Vec<u8>,
+}
+
+// the child writes into a pipe, a thread of its own moves it into the logfile
+// so that a busy event loop never blocks the child
+pub struct Output {
+    path: String,
+    // the program's user= and umask=, which a logfile taskmasterd creates gets
+    owner: Option<Uid>,
+    umask: u32,
+    pipe: Option<Arc<File>>,
+    sink: Arc<Mutex<Sink>>,
+}
+
+impl Output {
+    pub fn new(path: &str, owner: Option<Uid>, umask: u32) -> Self {
+        Output {
+            path: path.to_owned(),
+            owner,
+            umask,
+            pipe: None,
+            sink: Arc::new(Mutex::new(Sink::default())),
+        }
+    }
+
+    fn sink(&self) -> MutexGuard<'_, Sink> {
+        self.sink.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn open(&self, truncate: bool) -> std::io::Result<File> {
+        let created = !Path::new(&self.path).exists();
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(truncate)
+            .append(!truncate)
+            .open(&self.path)?;
+        if created {
+            file.set_permissions(Permissions::from_mode(0o666 & !self.umask))?;
+            if let Some(owner) = self.owner {
+                fchown(file.as_raw_fd(), Some(owner), None)?;
+            }
+        }
+        Ok(file)
+    }
+
+    pub fn open_logfile(&mut self) -> std::io::Result<()> {
+        let file = self.open(true)?;
+        self.sink().logfile = Some(file);
+        Ok(())
+    }
+
+    // logrotate moved the file away: keep writing at the same path
+    pub fn reopen_logfile(&mut self) -> std::io::Result<()> {
+        if self.sink().logfile.is_some() {
+            let file = self.open(false)?;
+            self.sink().logfile = Some(file);
+        }
+        Ok(())
+    }
+
+    pub fn pipe_fd(&self) -> Option<RawFd> {
+        self.pipe.as_ref().map(|p| p.as_raw_fd())
+    }
+
+    // take over a pipe inherited from the previous taskmasterd
+    pub fn adopt(&mut self, fd: RawFd) -> std::io::Result<()> {
+        let file = self.open(false)?;
+        self.sink().logfile = Some(file);
+        self.set_pipe(unsafe { OwnedFd::from_raw_fd(fd) });
+        Ok(())
+    }
+
+    pub fn set_pipe<T: Into<OwnedFd>>(&mut self, pipe: T) {
+        let pipe = Arc::new(File::from(pipe.into()));
+        // a pipe adopted from an older taskmasterd may still be nonblocking
+        if let Err(e) = fcntl(pipe.as_raw_fd(), FcntlArg::F_SETFL(OFlag::empty())) {
+            LOG.warn(&format!("set pipe blocking failed - {e}"));
+        }
+        let (reader, sink, path) = (pipe.clone(), self.sink.clone(), self.path.clone());
+        thread::spawn(move || pump(&reader, &sink, &path));
+        self.pipe = Some(pipe);
+    }
+
+    // what the child wrote since the last call, for attached clients and readiness
+    pub fn take_captured(&mut self, captured: &mut Vec<u8>) {
+        captured.append(&mut self.sink().captured);
+    }
+}
+
+// until the child and whatever inherited the pipe closed it
+fn pump(mut pipe: &File, sink: &Mutex<Sink>, path: &str) {
+    let mut buf = [0u8; PIPE_BUF_SIZE];
+    loop {
+        let n = match pipe.read(&mut buf) {
+            Ok(0) => return,
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => {
+                LOG.warn(&format!("read output for {path} failed - {e}"));
+                return;
+            }
+        };
+        let mut sink = sink.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(file) = sink.logfile.as_mut() {
+            if let Err(e) = file.write_all(&buf[..n]) {
+                LOG.warn(&format!("write to {path} failed - {e}"));
+            }
+        }
+        sink.captured.extend_from_slice(&buf[..n]);
+    }
+}
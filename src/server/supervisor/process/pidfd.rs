@@ -0,0 +1,94 @@
+use std::fs::read_to_string;
+use std::io::{self, ErrorKind};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::ptr;
+
+use nix::sys::signal::Signal;
+use nix::unistd::{Pid, Uid};
+
+// a process taskmasterd did not spawn, e.g. the daemon a forking launcher left behind.
+// it can not be waited for, but its pidfd keeps referring to it even if the pid is reused
+pub struct PidFd {
+    pid: Pid,
+    fd: OwnedFd,
+}
+
+impl PidFd {
+    pub fn open(pid: Pid) -> io::Result<Self> {
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid.as_raw(), 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(PidFd {
+            pid,
+            fd: unsafe { OwnedFd::from_raw_fd(fd as i32) },
+        })
+    }
+
+    // only a process running as `uid`, so that a program can name no one else's. any
+    // process for root, who could signal it anyway. a daemon may have left the
+    // launcher's session and been reparented to init, so its ancestry tells nothing.
+    // checked once the pidfd is open, so that the pid can not be reused in between
+    pub fn open_owned_by(pid: Pid, uid: Uid) -> io::Result<Self> {
+        if pid.as_raw() <= 1 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("pid {pid}"),
+            ));
+        }
+        let pidfd = PidFd::open(pid)?;
+        if !uid.is_root() {
+            let owner = real_uid(pid)?;
+            if owner != uid {
+                return Err(io::Error::new(
+                    ErrorKind::PermissionDenied,
+                    format!("pid {pid} runs as uid {owner}"),
+                ));
+            }
+        }
+        Ok(pidfd)
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    // a pidfd becomes readable once its process exited
+    pub fn exited(&self) -> bool {
+        let mut pollfd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut pollfd, 1, 0) > 0 }
+    }
+
+    pub fn send_signal(&self, signal: Signal) -> io::Result<()> {
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_pidfd_send_signal,
+                self.fd.as_raw_fd(),
+                signal as libc::c_int,
+                ptr::null::<libc::siginfo_t>(),
+                0,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+// the real uid of a process, from /proc/<pid>/status. unlike the owner of /proc/<pid>,
+// it stays the same when the process makes itself non-dumpable
+fn real_uid(pid: Pid) -> io::Result<Uid> {
+    let status = read_to_string(format!("/proc/{pid}/status"))?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))
+        .and_then(|uids| uids.split_whitespace().next())
+        .and_then(|uid| uid.parse().ok())
+        .map(Uid::from_raw)
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, format!("no uid for pid {pid}")))
+}
@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use lib::config::{ProgramConfig, ReadyProbe};
+
+use regex::Regex;
+
+use super::health::connect;
+
+// longest partial output line kept while waiting for its newline
+const MAX_LINE: usize = 4096;
+// a tcp probe which took longer is tried again
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+enum Probe {
+    Tcp(String),
+    File(PathBuf),
+    Log(Regex),
+    Notify,
+}
+
+// tells when a starting process is actually ready, instead of trusting startsecs
+pub struct Readiness {
+    probe: Probe,
+    line: Vec<u8>,
+    // a tcp probe resolves and connects in its own thread, like the health probes
+    attempt: Option<Receiver<bool>>,
+    ready: bool,
+}
+
+impl Readiness {
+    pub fn new(conf: &ProgramConfig) -> Option<Self> {
+        let probe = match conf.ready.as_ref()? {
+            ReadyProbe::Tcp(addr) => Probe::Tcp(addr.to_owned()),
+            ReadyProbe::File(path) => Probe::File(conf.resolve_path(path)),
+            ReadyProbe::Log(pattern) => Probe::Log(Regex::new(pattern).ok()?),
+            ReadyProbe::Notify => Probe::Notify,
+        };
+        Some(Readiness {
+            probe,
+            line: Vec::new(),
+            attempt: None,
+            ready: false,
+        })
+    }
+
+    pub fn reset(&mut self) {
+        self.line.clear();
+        self.attempt = None;
+        self.ready = false;
+    }
+
+    // output the process wrote since the last call, matched line by line
+    pub fn feed(&mut self, output: &[u8]) {
+        let Probe::Log(ref pattern) = self.probe else {
+            return;
+        };
+        if self.ready {
+            return;
+        }
+        self.line.extend_from_slice(output);
+
+        while let Some(end) = self.line.iter().position(|b| *b == b'\n') {
+            let rest = self.line.split_off(end + 1);
+            self.ready |= pattern.is_match(String::from_utf8_lossy(&self.line).trim_end());
+            self.line = rest;
+        }
+        if self.ready || self.line.len() > MAX_LINE {
+            self.line.clear();
+        }
+    }
+
+    // sd_notify READY=1
+    pub fn notified(&mut self) {
+        if let Probe::Notify = self.probe {
+            self.ready = true;
+        }
+    }
+
+    pub fn poll(&mut self) -> bool {
+        if !self.ready {
+            self.ready = match self.probe {
+                Probe::Tcp(ref addr) => {
+                    let attempt = self.attempt.get_or_insert_with(|| {
+                        let (tx, rx) = channel();
+                        let addr = addr.to_owned();
+                        thread::spawn(move || tx.send(connect(&addr, CONNECT_TIMEOUT).is_ok()));
+                        rx
+                    });
+                    match attempt.try_recv() {
+                        Err(TryRecvError::Empty) => false,
+                        connected => {
+                            self.attempt = None;
+                            connected.unwrap_or(false)
+                        }
+                    }
+                }
+                Probe::File(ref path) => path.exists(),
+                Probe::Log(_) | Probe::Notify => false,
+            };
+        }
+        self.ready
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::{Duration, Instant};
+
+use lib::config::ProgramConfig;
+use lib::logger::LOG;
+
+use crate::inotify::Inotify;
+
+// a rebuild writes many files, restart once they stopped changing for this long
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+// watch=: the files whose changes restart the process
+pub struct FileWatch {
+    inotify: Inotify,
+    changed_at: Option<Instant>,
+    paused: bool,
+}
+
+impl FileWatch {
+    pub fn new(id: &str, conf: &ProgramConfig) -> Option<Self> {
+        if conf.watch.is_empty() {
+            return None;
+        }
+        let mut inotify = match Inotify::new() {
+            Ok(o) => o,
+            Err(e) => {
+                LOG.warn(&format!("[{id}] failed to watch files - {e}"));
+                return None;
+            }
+        };
+        for path in conf.watch.iter() {
+            let path = conf.resolve_path(path);
+            if let Err(e) = inotify.add(&path) {
+                LOG.warn(&format!("[{id}] failed to watch {} - {e}", path.display()));
+            }
+        }
+        Some(FileWatch {
+            inotify,
+            changed_at: None,
+            paused: false,
+        })
+    }
+
+    // true once the watched files changed and settled
+    pub fn poll(&mut self) -> bool {
+        // events are read while paused too, so that resuming does not restart
+        if self.inotify.changed() && !self.paused {
+            self.changed_at = Some(Instant::now());
+        }
+        match self.changed_at {
+            Some(at) if at.elapsed() >= DEBOUNCE => {
+                self.changed_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.changed_at = None;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+}
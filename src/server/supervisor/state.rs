@@ -0,0 +1,159 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{create_dir_all, read_to_string, remove_file, rename, write};
+use std::io;
+use std::os::unix::io::RawFd;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use lib::logger::LOG;
+use lib::process_id::ProcessId;
+use lib::process_status::ProcessState;
+
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+use serde::{Deserialize, Serialize};
+
+// everything a re-executed taskmasterd needs to adopt a running process
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProcessSnapshot {
+    pub name: String,
+    pub seq: u32,
+    pub pid: Option<u32>,
+    pub state: ProcessState,
+    pub description: String,
+    // milliseconds since the unix epoch
+    pub started_at: Option<u64>,
+    pub current_try: u32,
+    pub exit_status: Option<i32>,
+    // MAINPID= or the daemon of a forking program, missing from older state files
+    #[serde(default)]
+    pub main_pid: Option<u32>,
+    pub stdin: Option<RawFd>,
+    pub stdout: Option<RawFd>,
+    pub stderr: Option<RawFd>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct State {
+    pub processes: Vec<ProcessSnapshot>,
+}
+
+impl State {
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        write(path, serde_json::to_string(self)?)
+    }
+
+    // the state file is consumed, a stale one must never be adopted twice
+    pub fn load(path: &str) -> io::Result<Self> {
+        let state = serde_json::from_str(&read_to_string(path)?)?;
+        remove_file(path)?;
+        Ok(state)
+    }
+
+    // pipes are close-on-exec by default, clear the flag right before exec
+    pub fn set_inheritable(&self, inheritable: bool) -> nix::Result<()> {
+        let flags = if inheritable {
+            FdFlag::empty()
+        } else {
+            FdFlag::FD_CLOEXEC
+        };
+        for p in self.processes.iter() {
+            for fd in [p.stdin, p.stdout, p.stderr].into_iter().flatten() {
+                fcntl(fd, FcntlArg::F_SETFD(flags))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+const INTENT_FILE: &str = "intent.json";
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum Intent {
+    Started,
+    Stopped,
+}
+
+// what the operator asked for with start/stop/restart, which wins over autostart.
+// kept in {statedir}/intent.json so that it survives restarts of taskmasterd.
+// how many instances run is numprocs= alone: nothing scales a program at runtime,
+// so there is no count to keep here
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Intents {
+    #[serde(skip)]
+    statedir: Option<String>,
+    processes: HashMap<String, Intent>,
+}
+
+impl Intents {
+    pub fn load(statedir: &Option<String>) -> Self {
+        let Some(dir) = statedir else {
+            return Intents::default();
+        };
+        let path = Path::new(dir).join(INTENT_FILE);
+        let mut intents = match read_to_string(&path) {
+            Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+                LOG.warn(&format!("ignore broken {} - {e}", path.display()));
+                Intents::default()
+            }),
+            Err(_) => Intents::default(),
+        };
+        intents.statedir = statedir.clone();
+        intents
+    }
+
+    pub fn get(&self, id: &ProcessId) -> Option<Intent> {
+        self.processes.get(&id.to_string()).copied()
+    }
+
+    pub fn set(&mut self, ids: &[ProcessId], intent: Intent) {
+        for id in ids.iter() {
+            self.processes.insert(id.to_string(), intent);
+        }
+        self.save();
+    }
+
+    // forget processes which are not configured anymore
+    pub fn retain(&mut self, ids: &HashSet<ProcessId>) {
+        let ids: HashSet<String> = ids.iter().map(|id| id.to_string()).collect();
+        self.processes.retain(|k, _| ids.contains(k));
+        self.save();
+    }
+
+    pub fn set_statedir(&mut self, statedir: &Option<String>) {
+        self.statedir = statedir.clone();
+    }
+
+    fn save(&self) {
+        let Some(dir) = self.statedir.as_ref() else {
+            return;
+        };
+        let path = Path::new(dir).join(INTENT_FILE);
+        let tmp = path.with_extension("json.tmp");
+        // write then rename, a crash must not leave a truncated file
+        let saved = create_dir_all(dir)
+            .and_then(|_| Ok(serde_json::to_string(self)?))
+            .and_then(|s| write(&tmp, s))
+            .and_then(|_| rename(&tmp, &path));
+        if let Err(e) = saved {
+            LOG.warn(&format!("failed to save {} - {e}", path.display()));
+        }
+    }
+}
+
+fn since_epoch() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+// Instant is only meaningful inside one process, the state file keeps wall clock time
+pub fn to_epoch_millis(at: Instant) -> u64 {
+    since_epoch().saturating_sub(at.elapsed()).as_millis() as u64
+}
+
+pub fn from_epoch_millis(millis: u64) -> Instant {
+    let elapsed = since_epoch().saturating_sub(Duration::from_millis(millis));
+    Instant::now()
+        .checked_sub(elapsed)
+        .unwrap_or_else(Instant::now)
+}
@@ -0,0 +1,4 @@
+# a legacy daemon launcher: fork into the background, leave a pidfile, exit
+sleep 1000 < /dev/null > /dev/null 2>&1 &
+echo $! > /tmp/taskmaster.forking.pid
+exit 0
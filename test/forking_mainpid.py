@@ -0,0 +1,19 @@
+import os
+import socket
+
+# a double forking daemon, told through MAINPID= instead of a pidfile
+r, w = os.pipe()
+if os.fork() == 0:
+    os.setsid()
+    pid = os.fork()
+    if pid == 0:
+        null = os.open(os.devnull, os.O_RDWR)
+        for fd in range(3):
+            os.dup2(null, fd)
+        os.execvp("sleep", ["sleep", "1000"])
+    os.write(w, str(pid).encode())
+    os._exit(0)
+os.wait()
+pid = os.read(r, 16).decode()
+sock = socket.socket(socket.AF_UNIX, socket.SOCK_DGRAM)
+sock.sendto(f"MAINPID={pid}".encode(), os.environ["NOTIFY_SOCKET"])
@@ -0,0 +1,3 @@
+# a double forking daemon: a session of its own, reparented to init once its parent exits
+setsid -w bash -c 'sleep 1000 < /dev/null > /dev/null 2>&1 & echo $! > /tmp/taskmaster.forking_setsid.pid'
+exit 0
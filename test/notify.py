@@ -0,0 +1,11 @@
+import os
+import socket
+import time
+
+# sd_notify: ready, then ping the watchdog three times and hang
+sock = socket.socket(socket.AF_UNIX, socket.SOCK_DGRAM)
+sock.sendto(b"READY=1\nSTATUS=serving", os.environ["NOTIFY_SOCKET"])
+for _ in range(3):
+    time.sleep(1)
+    sock.sendto(b"WATCHDOG=1", os.environ["NOTIFY_SOCKET"])
+time.sleep(1000)
@@ -0,0 +1,2 @@
+sleep 2
+exit 0
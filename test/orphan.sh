@@ -0,0 +1,7 @@
+# leave orphans behind, as a program which daemonizes would
+(sleep 1 &)
+(sleep 100 &)
+
+while true; do
+    sleep 1
+done
@@ -0,0 +1,3 @@
+sleep 2
+echo listening on 4242
+sleep 1000
@@ -0,0 +1,6 @@
+#!/bin/bash
+# notes when SIGTERM came, and exits on it unless told to ignore it
+trap 'echo "$1 $(date +%s.%N)" >> /tmp/taskmaster.stop_order; [ "$2" = ignore ] || exit 0' TERM
+while true; do
+    sleep 0.1
+done
@@ -1,8 +1,11 @@
 import re
+import json
+import socket
 import os
 import pwd
 import subprocess
 from time import sleep
+from datetime import datetime
 from functools import reduce
 
 import psutil
@@ -18,7 +21,7 @@ TMD = "target/debug/tmd"
 
 def get_ctl_result(tm, command):
     tm.sendline(command)
-    tm.expect(rf"{command}\r\n(.*)\r\ntaskmaster> ")
+    tm.expect(rf"{re.escape(command)}\r\n(.*)\r\ntaskmaster> ")
     return tm.match.group(1).decode("utf-8").strip()
 
 
@@ -86,6 +89,45 @@ def varying_text(request):
     yield change
 
 
+@pytest.fixture
+def include_dir():
+    path = '/tmp/taskmaster.autoreload.d'
+    os.makedirs(path, exist_ok=True)
+    with open(f'{path}/a.ini', 'w') as f:
+        f.write('[program:a]\ncommand = "/usr/bin/ls"\n')
+    # filled by the test, which runs as TEST_USER
+    uid = pwd.getpwnam(TEST_USER).pw_uid
+    os.chown(path, uid, -1)
+    os.chown(f'{path}/a.ini', uid, -1)
+    yield path
+    for name in os.listdir(path):
+        os.remove(f'{path}/{name}')
+    os.rmdir(path)
+
+
+@pytest.fixture
+def stop_order():
+    path = '/tmp/taskmaster.stop_order'
+    yield path
+    # written by the programs, removed once tm gave the euid back
+    os.remove(path)
+
+
+@pytest.fixture
+def watch_job():
+    with open('/tmp/taskmaster.watch_job.sh', 'w') as f:
+        f.write('#!/bin/sh\nsleep 2\n')
+    os.chmod('/tmp/taskmaster.watch_job.sh', 0o755)
+    # removed by the test, which runs as TEST_USER
+    os.chown('/tmp/taskmaster.watch_job.sh', pwd.getpwnam(TEST_USER).pw_uid, -1)
+    yield
+    for path in ['/tmp/taskmaster.watch_job.sh', '/tmp/taskmaster.watch']:
+        try:
+            os.remove(path)
+        except FileNotFoundError:
+            pass
+
+
 @pytest.mark.parametrize("tm", ["test/status_before_begin.ini"], indirect=True)
 def test_status_not_begin(tm):
 
@@ -752,3 +794,636 @@ def test_umask(tm):
     print(oct(stat_result.st_mode & 0o777))
     assert (stat_result.st_mode & 0o777) == 0o066
 
+
+
+@pytest.mark.parametrize("tm", ["test/sendstdin.ini"], indirect=True)
+def test_sendstdin(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    get_ctl_result(tm, 'start cat:0')
+    sleep(2)
+
+    output = get_ctl_result(tm, 'sendstdin cat:0 hello world')
+    print(output)
+    assert output == 'cat:0: sent'
+
+    sleep(1)
+    with open('/tmp/sendstdin.log') as f:
+        assert f.read() == 'hello world\n'
+
+
+@pytest.mark.parametrize("tm", ["test/sendstdin.ini"], indirect=True)
+def test_sendstdin_fail(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    output = get_ctl_result(tm, 'sendstdin cat:0 hello')
+    print(output)
+    assert output == 'cat:0: not running.'
+
+    get_ctl_result(tm, 'start no_pipe:0')
+    sleep(2)
+
+    output = get_ctl_result(tm, 'sendstdin no_pipe:0 hello')
+    print(output)
+    assert output == 'no_pipe:0: stdin is not a pipe.'
+
+
+@pytest.mark.parametrize("tm", ["test/fg.ini"], indirect=True)
+def test_fg(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    get_ctl_result(tm, 'start tick:0')
+    sleep(2)
+
+    tm.sendline('fg tick:0')
+    tm.expect(r'tick:0: attached')
+    tm.expect(r'tick\r\n')
+
+    # Ctrl-C detaches and comes back to the prompt
+    tm.sendcontrol('c')
+    tm.expect(r'taskmaster> ')
+
+    output = get_ctl_result(tm, 'status tick:0')
+    print(output)
+    assert re.match(r'tick:0\s+Running', output)
+
+
+@pytest.mark.parametrize("tm", ["test/signal.ini"], indirect=True)
+def test_signal(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    output = get_ctl_result(tm, 'signal QUIT sig_echo:0')
+    print(output)
+    assert output == 'sig_echo:0: not running.'
+
+    get_ctl_result(tm, 'start sig_echo:0')
+    sleep(2)
+
+    output = get_ctl_result(tm, 'signal QUIT sig_echo:*')
+    print(output)
+    assert output == 'sig_echo:0: signalled'
+
+    sleep(2)
+    with open('/tmp/sig_echo.log') as f:
+        assert f.read() == 'QUIT\n'
+
+
+@pytest.mark.parametrize("tm", ["test/start_simple_success.ini"], indirect=True)
+def test_already_running(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    # second taskmasterd should not start while the first holds the pidfile
+    result = subprocess.run([TMD, "test/start_simple_success.ini"],
+                            capture_output=True)
+    print(result.stdout)
+    assert result.returncode == 1
+    assert b'already running' in result.stdout
+
+    # first one is still serving
+    output = get_ctl_result(tm, 'status')
+    assert re.match(r"tailf:0\s+", output)
+
+
+@pytest.mark.parametrize("tm", ["test/reopenlogs.ini"], indirect=True)
+def test_reopenlogs(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    get_ctl_result(tm, 'start tick:0')
+    sleep(2)
+    before = get_ctl_result(tm, 'status tick:0')
+
+    # what logrotate does
+    os.rename('/tmp/tick.log', '/tmp/tick.log.1')
+    output = get_ctl_result(tm, 'reopenlogs')
+    print(output)
+    assert output == 'taskmasterd: logs reopened'
+
+    sleep(1)
+    with open('/tmp/tick.log') as f:
+        assert 'tick' in f.read()
+
+    # process kept running with the same pid
+    after = get_ctl_result(tm, 'status tick:0')
+    assert re.search(r"pid \d+", before).group() == re.search(r"pid \d+", after).group()
+
+
+@pytest.mark.parametrize("tm", ["test/reopenlogs_stdout.ini"], indirect=True)
+def test_reopenlogs_stdout(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    # the daemon logs to its stdout, which is the logfile
+    os.rename('/tmp/taskmaster.log', '/tmp/taskmaster.log.1')
+    output = get_ctl_result(tm, 'reopenlogs')
+    assert output == 'taskmasterd: logs reopened'
+
+    get_ctl_result(tm, 'status')
+    with open('/tmp/taskmaster.log') as f:
+        assert 'handle request - status' in f.read()
+    os.remove('/tmp/taskmaster.log.1')
+
+
+@pytest.mark.parametrize("tm", ["test/fg.ini"], indirect=True)
+def test_reexec(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    get_ctl_result(tm, 'start tick:0')
+    sleep(2)
+    before = get_ctl_result(tm, 'status tick:0')
+
+    output = get_ctl_result(tm, 'reexec')
+    print(output)
+    assert output == 'taskmasterd: reexec'
+    sleep(1)
+
+    # the new taskmasterd adopted the process instead of restarting it
+    after = get_ctl_result(tm, 'status tick:0')
+    print(after)
+    assert re.match(r"tick:0\s+Running\s+", after)
+    assert re.search(r"pid \d+", before).group() == re.search(r"pid \d+", after).group()
+
+
+@pytest.mark.parametrize("tm", ["test/reexec_backoff.ini"], indirect=True)
+def test_reexec_backoff(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+    sleep(1)
+
+    # flap keeps exiting, so it is often handed over in backoff, without a pid
+    output = get_ctl_result(tm, 'reexec')
+    assert output == 'taskmasterd: reexec'
+    sleep(0.5)
+
+    get_ctl_result(tm, 'stop flap:0')
+    sleep(1)
+    output = get_ctl_result(tm, 'status flap:0')
+    assert re.match(r"flap:0\s+Stopped", output)
+
+
+@pytest.mark.parametrize("tm", ["test/statedir.ini"], indirect=True)
+def test_statedir(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    get_ctl_result(tm, 'stop tailf:0')
+    sleep(1)
+    get_ctl_result(tm, 'shutdown')
+    sleep(1)
+
+    # tailf was stopped by hand, autostart must not bring it back
+    subprocess.run([TMD, "test/statedir.ini"])
+    sleep(1)
+    output = get_ctl_result(tm, 'status tailf:0')
+    print(output)
+    assert re.match(r"tailf:0\s+Stopped\s+", output)
+
+    get_ctl_result(tm, 'start tailf:0')
+    os.remove('/tmp/taskmaster.state/intent.json')
+
+
+@pytest.mark.parametrize("tm", ["test/shutdown_timeout.ini"], indirect=True)
+def test_shutdown_timeout(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+    sleep(2)
+
+    # ign_term ignores SIGTERM, it is killed after shutdown_timeout
+    # instead of stopwaitsecs, and shutdown answers only when it is done
+    output = get_ctl_result(tm, 'shutdown')
+    assert output == 'taskmasterd: shutdown'
+
+    sleep(1)
+    assert not any(map(is_tmd, psutil.process_iter()))
+    assert not any('ign_term.sh' in ' '.join(p.cmdline())
+                   for p in psutil.process_iter())
+
+
+@pytest.mark.parametrize("tm", ["test/stop_order.ini"], indirect=True)
+def test_shutdown_order(stop_order, tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+    sleep(3)
+
+    # second started last and is stopped first. first gets SIGTERM only once
+    # second is gone, which ignores it until its stopwaitsecs are over
+    output = get_ctl_result(tm, 'shutdown')
+    assert output == 'taskmasterd: shutdown'
+
+    with open(stop_order) as f:
+        lines = [line.split() for line in f.read().splitlines()]
+    assert [name for name, _ in lines] == ['second', 'first']
+    assert float(lines[1][1]) - float(lines[0][1]) >= 1.9
+
+
+def test_init_mode():
+    is_root_or_exit()
+    # init mode stays in the foreground, as the first process of a container
+    os.seteuid(pwd.getpwnam(TEST_USER).pw_uid)
+    tmd = subprocess.Popen([TMD, "test/init.ini"])
+    sleep(3)
+    tm = pexpect.spawn(TMCTL, ["test/init.ini"], timeout=TIMEOUT)
+    try:
+        # ignore strings before first prompt
+        tm.expect(r".*taskmaster> ")
+
+        # orphans of the program are reparented to taskmasterd, which reaps them
+        children = psutil.Process(tmd.pid).children()
+        print(children)
+        assert any('sleep' in p.name() for p in children)
+        assert all(p.status() != psutil.STATUS_ZOMBIE for p in children)
+
+        # and terminates the ones still alive when it shuts down
+        output = get_ctl_result(tm, 'shutdown')
+        assert output == 'taskmasterd: shutdown'
+        tmd.wait(TIMEOUT)
+        assert not any(p.is_running() for p in children)
+    finally:
+        cleanup()
+
+
+@pytest.mark.parametrize("tm", ["test/critical.ini"], indirect=True)
+def test_critical(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    # main exits with 3 after 2 seconds, taskmasterd goes down with it
+    sleep(4)
+    assert not any(map(is_tmd, psutil.process_iter()))
+
+    result = subprocess.run([TMD, "-n", "test/critical.ini"], timeout=TIMEOUT)
+    assert result.returncode == 3
+
+
+@pytest.mark.parametrize("tm", ["test/healthcheck.ini"], indirect=True)
+def test_healthcheck(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    # wedged fails its check twice after it is running, autorestart=never keeps it
+    sleep(4)
+    output = get_ctl_result(tm, 'status wedged:0')
+    assert re.match(r'wedged:0\s+Unhealthy\s+pid \d+, uptime 0:00:\d\d', output)
+    output = get_ctl_result(tm, 'status healthy:0')
+    assert re.match(r'healthy:0\s+Running\s+pid \d+, uptime 0:00:\d\d', output)
+
+
+@pytest.mark.parametrize("tm", ["test/ready.ini"], indirect=True)
+def test_ready(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    # start answers once the log line shows up, well before startsecs
+    output = get_ctl_result(tm, 'start ready:0')
+    assert output == 'ready:0: started'
+    output = get_ctl_result(tm, 'status ready:0')
+    assert re.match(r'ready:0\s+Running\s+pid \d+, uptime 0:00:0[23]', output)
+
+    # never is killed after startsecs and gives up
+    output = get_ctl_result(tm, 'start never:0')
+    assert output == 'never:0: did not get ready.'
+    output = get_ctl_result(tm, 'status never:0')
+    assert re.match(r'never:0\s+Fatal\s+Not ready in time.', output)
+
+
+@pytest.mark.parametrize("tm", ["test/notify.ini"], indirect=True)
+def test_notify_watchdog(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+    sleep(1)
+
+    # READY=1 ends Starting, STATUS= shows up in status
+    output = get_ctl_result(tm, 'status notify:0')
+    match = re.match(r'notify:0\s+Running\s+pid (\d+), uptime 0:00:0\d - serving', output)
+    assert match
+    pid = match.group(1)
+
+    # pings stop after 3 seconds, watchdog_secs later it is restarted
+    sleep(5)
+    output = get_ctl_result(tm, 'status notify:0')
+    match = re.match(r'notify:0\s+Running\s+pid (\d+), uptime 0:00:0\d - serving', output)
+    assert match
+    assert match.group(1) != pid
+
+
+@pytest.mark.parametrize("tm", ["test/watchdog_secs_max.ini"], indirect=True)
+def test_watchdog_secs_max(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+    sleep(2)
+
+    # WATCHDOG_USEC does not fit, the largest value is passed instead
+    output = get_ctl_result(tm, 'status app:0')
+    assert re.match(r'app:0\s+Running\s+pid \d+', output)
+
+
+@pytest.mark.parametrize("tm", ["test/forking.ini"], indirect=True)
+def test_forking(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+    sleep(2)
+
+    # the launcher exited, the daemon from the pidfile is what runs
+    with open("/tmp/taskmaster.forking.pid") as f:
+        pid = f.read().strip()
+    output = get_ctl_result(tm, 'status forking:0')
+    assert re.match(rf'forking:0\s+Running\s+pid {pid}, uptime 0:00:0\d', output)
+
+    # stop signals the daemon
+    output = get_ctl_result(tm, 'stop forking:0')
+    assert output == 'forking:0: stopping'
+    sleep(1)
+    output = get_ctl_result(tm, 'status forking:0')
+    assert re.match(r'forking:0\s+Stopped', output)
+    assert not psutil.pid_exists(int(pid)) or \
+        psutil.Process(int(pid)).status() == psutil.STATUS_ZOMBIE
+
+
+@pytest.mark.parametrize("tm", ["test/forking_setsid.ini"], indirect=True)
+def test_forking_setsid(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+    sleep(2)
+
+    # the daemon left the launcher's session and has no parent from it anymore
+    with open("/tmp/taskmaster.forking_setsid.pid") as f:
+        pid = f.read().strip()
+    tmd = next(p for p in psutil.process_iter() if p.cmdline()[:1] == [TMD])
+    assert os.getsid(int(pid)) != os.getsid(tmd.pid)
+    assert psutil.Process(int(pid)).ppid() != tmd.pid
+    output = get_ctl_result(tm, 'status forking_setsid:0')
+    assert re.match(rf'forking_setsid:0\s+Running\s+pid {pid}, uptime 0:00:0\d', output)
+
+    output = get_ctl_result(tm, 'stop forking_setsid:0')
+    assert output == 'forking_setsid:0: stopping'
+    sleep(1)
+    output = get_ctl_result(tm, 'status forking_setsid:0')
+    assert re.match(r'forking_setsid:0\s+Stopped', output)
+    assert not psutil.pid_exists(int(pid)) or \
+        psutil.Process(int(pid)).status() == psutil.STATUS_ZOMBIE
+
+
+@pytest.mark.parametrize("tm", ["test/forking_mainpid.ini"], indirect=True)
+def test_forking_mainpid(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+    sleep(2)
+
+    # MAINPID= names a daemon reparented to init, in a session of its own
+    output = get_ctl_result(tm, 'status forking_mainpid:0')
+    match = re.match(r'forking_mainpid:0\s+Running\s+pid (\d+), uptime 0:00:0\d', output)
+    assert match
+    pid = int(match.group(1))
+    assert psutil.Process(pid).cmdline() == ['sleep', '1000']
+    tmd = next(p for p in psutil.process_iter() if p.cmdline()[:1] == [TMD])
+    assert os.getsid(pid) != os.getsid(tmd.pid)
+
+    output = get_ctl_result(tm, 'stop forking_mainpid:0')
+    assert output == 'forking_mainpid:0: stopping'
+    sleep(1)
+    output = get_ctl_result(tm, 'status forking_mainpid:0')
+    assert re.match(r'forking_mainpid:0\s+Stopped', output)
+    assert not psutil.pid_exists(pid) or \
+        psutil.Process(pid).status() == psutil.STATUS_ZOMBIE
+
+
+@pytest.mark.parametrize("tm", ["test/schedule.ini"], indirect=True)
+def test_schedule(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    # the job runs at the start of the next minute, then waits for the one after
+    sleep(62 - datetime.now().second)
+    output = get_ctl_result(tm, 'status job:0')
+    assert re.match(
+        r'job:0\s+Exited\s+[\d\-: .]+ \(last run [\d\-: .]+, exit code 0\)', output)
+
+
+@pytest.mark.parametrize("tm", ["test/schedule_spawn_fail.ini"], indirect=True)
+def test_schedule_spawn_fail(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    # a run which fails to spawn leaves the job Fatal, taskmasterd keeps going
+    sleep(62 - datetime.now().second)
+    output = get_ctl_result(tm, 'status job:0')
+    assert re.match(r'job:0\s+Fatal\s+spawn failed', output)
+
+
+@pytest.mark.parametrize("tm", ["test/oneshot.ini"], indirect=True)
+def test_oneshot_depends_on(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    # web waits until migrate is done
+    output = get_ctl_result(tm, 'status web:0')
+    assert re.match(r'web:0\s+Stopped\s+Waiting for migrate', output)
+
+    sleep(4)
+    output = get_ctl_result(tm, 'status migrate:0')
+    assert re.match(r'migrate:0\s+Completed', output)
+    output = get_ctl_result(tm, 'status web:0')
+    assert re.match(r'web:0\s+Running\s+pid \d+, uptime 0:00:0\d', output)
+
+
+@pytest.mark.parametrize("tm", ["test/watch.ini"], indirect=True)
+def test_watch(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+    sleep(2)
+
+    output = get_ctl_result(tm, 'status app:0')
+    pid = re.match(r'app:0\s+Running\s+pid (\d+)', output).group(1)
+
+    # a change restarts it, once the file settled
+    with open('/tmp/taskmaster.watch', 'w') as f:
+        f.write('1')
+    sleep(3)
+    output = get_ctl_result(tm, 'status app:0')
+    restarted = re.match(r'app:0\s+Running\s+pid (\d+)', output).group(1)
+    assert restarted != pid
+
+    output = get_ctl_result(tm, 'watch pause app:0')
+    assert output == 'app:0: watch paused'
+    with open('/tmp/taskmaster.watch', 'w') as f:
+        f.write('2')
+    sleep(3)
+    output = get_ctl_result(tm, 'status app:0')
+    assert re.match(r'app:0\s+Running\s+pid ' + restarted, output)
+    os.remove('/tmp/taskmaster.watch')
+
+
+@pytest.mark.parametrize("tm", ["test/watch_spawn_fail.ini"], indirect=True)
+def test_watch_spawn_fail(watch_job, tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+    sleep(4)
+
+    output = get_ctl_result(tm, 'status job:0')
+    assert re.match(r'job:0\s+Exited', output)
+
+    # the change starts it again, which fails with the command gone
+    os.remove('/tmp/taskmaster.watch_job.sh')
+    with open('/tmp/taskmaster.watch', 'w') as f:
+        f.write('1')
+    sleep(3)
+    output = get_ctl_result(tm, 'status job:0')
+    assert re.match(r'job:0\s+Fatal\s+spawn failed', output)
+
+
+@pytest.mark.parametrize("varying_text", [("test/autoreload.ini", "test/autoreload_origin.ini", "test/autoreload_modified.ini")], indirect=True)
+@pytest.mark.parametrize("tm", ["test/autoreload.ini"], indirect=True)
+def test_autoreload(varying_text, tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    output = get_ctl_result(tm, 'status')
+    assert re.match(r"a:0\s+Stopped\s+Not started", output)
+
+    # updated without asking
+    varying_text()
+    sleep(2)
+    output = get_ctl_result(tm, 'status')
+    assert re.match(r"b:0\s+Stopped\s+Not started", output)
+
+    # an invalid config is left alone
+    overwrite("test/autoreload.ini", "test/autoreload_invalid.ini")
+    sleep(2)
+    output = get_ctl_result(tm, 'status')
+    assert re.match(r"b:0\s+Stopped\s+Not started", output)
+
+
+@pytest.mark.parametrize("tm", ["test/autoreload_include.ini"], indirect=True)
+def test_autoreload_include(include_dir, tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    output = get_ctl_result(tm, 'status')
+    assert re.match(r"a:0\s+Stopped\s+Not started", output)
+
+    # a new file matching the pattern is included
+    with open(f'{include_dir}/b.ini', 'w') as f:
+        f.write('[program:b]\ncommand = "/usr/bin/ls"\n')
+    sleep(2)
+    output = get_ctl_result(tm, 'status b:0')
+    assert re.match(r"b:0\s+Stopped\s+Not started", output)
+
+    # and watched from then on
+    with open(f'{include_dir}/b.ini', 'w') as f:
+        f.write('[program:c]\ncommand = "/usr/bin/ls"\n')
+    sleep(2)
+    output = get_ctl_result(tm, 'status c:0')
+    assert re.match(r"c:0\s+Stopped\s+Not started", output)
+
+
+@pytest.mark.parametrize("tm", ["test/rolling.ini"], indirect=True)
+def test_restart_rolling(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+    sleep(2)
+
+    # the last instance is restarted once the first two are Running again
+    output = get_ctl_result(tm, 'restart --rolling --batch 2 web:*')
+    assert output.split('\r\n') == [
+        'web:0: stopping', 'web:1: stopping', 'web:0: started', 'web:1: started',
+        'web:2: stopping', 'web:2: started']
+
+    output = get_ctl_result(tm, 'status')
+    assert len(re.findall(r'web:\d\s+Running', output)) == 3
+
+    output = get_ctl_result(tm, 'restart --batch 2 web:*')
+    assert output.startswith('Error: --batch and --wait-running go with --rolling')
+
+
+@pytest.mark.parametrize("tm", ["test/stopwaitsecs1.ini"], indirect=True)
+def test_wait(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    output = get_ctl_result(tm, 'start --wait ign_term:0')
+    assert output == 'ign_term:0: Running'
+
+    # SIGTERM is ignored, it takes stopwaitsecs and SIGKILL
+    output = get_ctl_result(tm, 'stop --wait --timeout 1 ign_term:0')
+    assert output == 'ign_term:0: still Stopping, timed out.'
+    sleep(2)
+
+    get_ctl_result(tm, 'start ign_term:0')
+    output = get_ctl_result(tm, 'stop --wait ign_term:0')
+    assert output == 'ign_term:0: Stopped (killed)'
+
+    output = get_ctl_result(tm, 'start --timeout 1 ign_term:0')
+    assert output.startswith('Error: --timeout goes with --wait')
+    output = get_ctl_result(tm, 'restart --rolling --wait ign_term:0')
+    assert output.startswith('Error: --rolling waits by itself, without --wait')
+
+
+@pytest.mark.parametrize("tm", ["test/rolling.ini"], indirect=True)
+def test_wait_command(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    result = subprocess.run(
+        [TMCTL, "test/rolling.ini", "wait", "running", "web:*"], capture_output=True)
+    assert result.returncode == 0
+    assert result.stdout.decode().split('\n')[:3] == [
+        'web:0: Running', 'web:1: Running', 'web:2: Running']
+
+    result = subprocess.run(
+        [TMCTL, "test/rolling.ini", "wait", "stopped", "web:0", "--timeout", "1"],
+        capture_output=True)
+    assert result.returncode == 1
+    assert result.stderr.decode().strip() == 'web:0: still Running, timed out.'
+
+    output = get_ctl_result(tm, 'wait sleeping web:0')
+    assert output.startswith('Error: wait requires a state')
+
+
+@pytest.mark.parametrize("tm", ["test/rolling.ini"], indirect=True)
+def test_protocol(tm):
+    # ignore strings before first prompt
+    tm.expect(r".*taskmaster> ")
+
+    # several requests on one connection, each reply carries its request id
+    with socket.socket(socket.AF_UNIX) as sock:
+        sock.connect("/tmp/taskmaster.sock")
+        conn = sock.makefile("rwb")
+        conn.write(b'{"version":2}\n')
+        conn.write(b'{"id":3,"method":"status","params":{"names":["web:0"]}}\n')
+        conn.write(b'{"id":4,"method":"stop","params":{"names":["web:1"]}}\n')
+        # params are checked against what the method takes
+        conn.write(b'{"id":5,"method":"stop","params":{"names":"web:2"}}\n')
+        conn.write(b'{"id":6,"method":"stop","params":{"names":["web:2"],"timeout":3}}\n')
+        conn.flush()
+        assert json.loads(conn.readline()) == {"version": 2}
+        assert json.loads(conn.readline())["id"] == 3
+        reply = json.loads(conn.readline())
+        assert reply["id"] == 4
+        assert reply["response"]["Action"]["list"][0]["Ok"]["name"] == "web:1"
+        for id in [5, 6]:
+            reply = json.loads(conn.readline())
+            assert reply["id"] == id
+            assert reply["response"]["Action"]["list"] == [
+                {"Err": {"InvalidRequest": "argument"}}]
+
+    # a client from before the handshake gets an error instead of a hang
+    with socket.socket(socket.AF_UNIX) as sock:
+        sock.connect("/tmp/taskmaster.sock")
+        sock.sendall(b'{"method":"status","args":[]}')
+        reply = json.loads(sock.makefile("rb").read())
+        assert reply == {"Action": {"list": [{"Err": {"ProtocolMismatch": [0, 2]}}]}}
+
+    # a newer client is told the version spoken, then disconnected
+    with socket.socket(socket.AF_UNIX) as sock:
+        sock.connect("/tmp/taskmaster.sock")
+        conn = sock.makefile("rwb")
+        conn.write(b'{"version":3}\n')
+        conn.flush()
+        assert json.loads(conn.readline()) == {"version": 2}
+        assert conn.readline() == b""
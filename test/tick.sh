@@ -0,0 +1,4 @@
+while true; do
+    echo tick
+    sleep 0.3
+done